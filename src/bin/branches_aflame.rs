@@ -3,13 +3,31 @@ use clap::Parser;
 use indicatif::ProgressIterator;
 use na::Vector2;
 use noise::{NoiseFn, Simplex};
-use palette::{Gradient, LinSrgb};
-use rand::{distributions::Uniform, prelude::Distribution, Rng, SeedableRng};
+use palette::LinSrgb;
+use proc_art::{
+    flow::{
+        Constant, EvenSpacingArgs, FlowArgs, FlowField, IntegratorArgs, Normalize, ScalePosition,
+        Sum,
+    },
+    noise::{frame_path, AnimationArgs, GridInterpolation, NoiseArgs, SampledField},
+    points::{Density, SeedingArgs},
+    ribbon::{fill_ribbon, RibbonArgs},
+    skia_colors::{linear_to_color, PaletteArgs, PaletteGradient},
+};
+use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use std::path::Path;
+use std::process;
 use tiny_skia::{Color, Paint, PathBuilder, Pixmap, Stroke, Transform};
 
+/// Draw walks through a noise flow field, colored along a gradient
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about=None)]
+#[command(mut_arg("noise_scale", |arg| arg.default_value("0.01")))]
+#[command(mut_arg("palette", |arg| arg.help(
+    "bundled palette name, palette file or generator (gen:triadic) to build the walk color gradient from"
+)))]
+#[command(mut_arg("palette_lookup", |arg| arg.default_value("continuous")))]
 struct Args {
     /// output path
     #[arg(short, long, default_value_t = String::from("branches_aflame.png"))]
@@ -27,79 +45,80 @@ struct Args {
     #[arg(long)]
     seed: Option<u64>,
 
-    #[arg(long, default_value_t = 0.4)]
+    /// constant flow added to the noise flow, before normalizing
+    #[arg(long, default_value_t = 0.4, allow_negative_numbers = true)]
     bias_x: f64,
 
-    #[arg(long, default_value_t = 0.3)]
+    #[arg(long, default_value_t = 0.4, allow_negative_numbers = true)]
     bias_y: f64,
 
-    /// noise scale
-    #[arg(long, default_value_t = 100.)]
-    scale: f64,
-
     #[arg(long, default_value_t = false)]
     draw_flow_tails: bool,
 
     #[arg(long, default_value_t = true)]
     draw_flow_walks: bool,
 
+    /// walks to draw, or roughly how many with poisson and grid seeding
     #[arg(long, default_value_t = 2000)]
     flow_walk_n: u32,
 
-    #[arg(long, default_value_t = 1000)]
+    /// integration steps per walk, with up to --flow-walk-step-size pixels per step
+    #[arg(long, default_value_t = 3000)]
     flow_walk_steps: u32,
 
     #[arg(long, default_value_t = 4.)]
     flow_walk_step_size: f64,
 
+    #[command(flatten)]
+    integrator: IntegratorArgs,
+
+    #[command(flatten)]
+    seeding: SeedingArgs,
+
+    #[command(flatten)]
+    ribbon: RibbonArgs,
+
+    #[command(flatten)]
+    spacing: EvenSpacingArgs,
+
+    #[command(flatten)]
+    flow: FlowArgs,
+
+    /// sample flow onto a grid once and interpolate it, instead of evaluating noise at every step
+    #[arg(long, value_enum)]
+    flow_cache: Option<GridInterpolation>,
+
+    /// distance between cached flow samples, in pixels
+    #[arg(long, default_value_t = 2., value_parser = parse_step)]
+    flow_cache_step: f64,
+
+    /// save the sampled flow grid, as an EXR image if the path ends in .exr
+    #[arg(long, conflicts_with = "frames")]
+    save_flow: Option<String>,
+
+    /// draw with a flow grid saved by --save-flow instead of generating noise
+    #[arg(long, conflicts_with = "frames")]
+    load_flow: Option<String>,
+
     #[arg(long, default_value_t = 10.)]
     color_scale: f64,
 
     #[arg(long, default_value_t = 48.)]
     color_range: f64,
-}
 
-struct Noise2x2 {
-    /// position scale.  Coordinates are multiplied by this value before being passed to noise functions.
-    pub pos_scale: f64,
-    pub normalize: bool,
-    pub bias: Vector2<f64>,
-    noise_x: Box<dyn NoiseFn<f64, 2>>,
-    noise_y: Box<dyn NoiseFn<f64, 2>>,
-}
+    #[command(flatten)]
+    palette: PaletteArgs,
 
-impl Noise2x2 {
-    fn new(rng: &mut impl Rng) -> Self {
-        Noise2x2 {
-            pos_scale: 1.,
-            normalize: false,
-            bias: Vector2::zeros(),
-            noise_x: Box::new(Simplex::new(rng.gen())),
-            noise_y: Box::new(Simplex::new(rng.gen())),
-        }
-    }
+    #[command(flatten)]
+    noise: NoiseArgs,
 
-    fn sample(&self, pos: &Vector2<f64>) -> Vector2<f64> {
-        let pos = pos / self.pos_scale;
-        let mut out = Vector2::new(
-            self.noise_x.get([pos.x, pos.y]),
-            self.noise_y.get([pos.x, pos.y]),
-        );
-        out += self.bias;
-        if self.normalize && out.norm() > 0. {
-            out.normalize_mut();
-        }
-        out
-    }
+    #[command(flatten)]
+    animation: AnimationArgs,
 }
 
 pub fn main() {
     let args = Args::parse();
 
-    // set up canvas
-    let mut pixmap = Pixmap::new(args.width, args.height).unwrap();
-    pixmap.fill(Color::from_rgba8(255, 255, 255, 255));
-
     // set up RNG
     let mut rng = match args.seed {
         Some(n) => ChaCha8Rng::seed_from_u64(n),
@@ -107,11 +126,142 @@ pub fn main() {
     };
 
     // set up flow noise
-    let mut flow_noise = Noise2x2::new(&mut rng);
-    flow_noise.pos_scale = args.scale;
-    flow_noise.normalize = true;
-    // todo: args
-    flow_noise.bias = Vector2::new(0.4, 0.4);
+    let [seed_x, seed_y] = args.noise.seeds(&mut rng);
+    let get_field = |frame| {
+        let build = |seed| {
+            args.noise.build_unscaled(seed, frame).unwrap_or_else(|e| {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            })
+        };
+        args.flow.build(build(seed_x), build(seed_y))
+    };
+    let size = [args.width as f64, args.height as f64];
+    let density = args
+        .seeding
+        .density(size, || {
+            // the rng only advances when noise density is asked for
+            let [.., seed] = args.noise.seeds::<3>(&mut rng);
+            args.noise.build(seed, size, None).unwrap_or_else(|e| {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            })
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("error: could not load seed density: {}", e);
+            process::exit(1);
+        });
+
+    match args.animation.animation() {
+        Some(animation) => {
+            // every frame starts from the same rng state, so walks start in the same places
+            for (i, time) in animation.times().enumerate() {
+                let field = get_field(Some((&animation, time)));
+                let pixmap = draw(
+                    &args,
+                    rng.clone(),
+                    cache_flow(&args, field),
+                    density.as_deref(),
+                );
+                pixmap.save_png(frame_path(&args.out, i)).unwrap();
+            }
+        }
+        None => {
+            let field = match &args.load_flow {
+                Some(path) => load_flow(&args, path),
+                None => cache_flow(&args, get_field(None)),
+            };
+            let pixmap = draw(&args, rng, field, density.as_deref());
+            pixmap.save_png(&args.out).unwrap();
+        }
+    }
+}
+
+/// Noise units per pixel.
+fn flow_scale(args: &Args) -> f64 {
+    args.noise.scale([args.width as f64, args.height as f64])
+}
+
+/// Parse `--flow-cache-step`, which must be positive.
+fn parse_step(s: &str) -> Result<f64, String> {
+    let step: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if step.is_finite() && step > 0. {
+        Ok(step)
+    } else {
+        Err(String::from("must be a positive number"))
+    }
+}
+
+/// Extent of the image in noise coordinates.
+fn flow_domain(args: &Args) -> [f64; 2] {
+    [args.width, args.height].map(|n| n as f64 * flow_scale(args))
+}
+
+/// Sample `field` over the image if `--flow-cache` or `--save-flow` was given, saving it
+/// if asked to.
+fn cache_flow(
+    args: &Args,
+    field: Box<dyn FlowField + Send + Sync>,
+) -> Box<dyn FlowField + Send + Sync> {
+    if args.flow_cache.is_none() && args.save_flow.is_none() {
+        return field;
+    }
+    let resolution =
+        [args.width, args.height].map(|n| (n as f64 / args.flow_cache_step).ceil() as usize + 1);
+    let sampled = SampledField::from_field(&field, [0., 0.], flow_domain(args), resolution)
+        .set_interpolation(args.flow_cache.unwrap_or_default());
+    if let Some(path) = &args.save_flow {
+        let saved = if is_exr(path) {
+            sampled.save_exr(path).map_err(|e| e.to_string())
+        } else {
+            sampled.save(path).map_err(|e| e.to_string())
+        };
+        if let Err(e) = saved {
+            eprintln!("error: could not save flow to {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    Box::new(sampled)
+}
+
+/// Load a flow grid saved by `--save-flow`.  EXR grids are stretched over the image.
+fn load_flow(args: &Args, path: &str) -> Box<dyn FlowField + Send + Sync> {
+    let sampled = if is_exr(path) {
+        SampledField::load_exr(path, [0., 0.], flow_domain(args))
+    } else {
+        SampledField::load(path)
+    };
+    let sampled = sampled
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}: {}", path, e);
+            process::exit(1);
+        })
+        .set_interpolation(args.flow_cache.unwrap_or_default());
+    Box::new(sampled)
+}
+
+fn is_exr(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"))
+}
+
+fn draw(
+    args: &Args,
+    mut rng: ChaCha8Rng,
+    field: Box<dyn FlowField + Send + Sync>,
+    density: Option<&(dyn Density + Send + Sync)>,
+) -> Pixmap {
+    // set up canvas
+    let mut pixmap = Pixmap::new(args.width, args.height).unwrap();
+    pixmap.fill(Color::from_rgba8(255, 255, 255, 255));
+
+    // walk in pixels, at unit speed
+    let raw_flow = Sum::new(
+        ScalePosition::new(field, flow_scale(args)),
+        Constant(Vector2::new(args.bias_x, args.bias_y)),
+    );
+    let flow = Normalize::new(&raw_flow);
 
     // draw flow tails
     // todo: arg gate
@@ -126,8 +276,10 @@ pub fn main() {
 
         let transform = Transform::identity();
 
-        let mut stroke = Stroke::default();
-        stroke.width = 1.0;
+        let stroke = Stroke {
+            width: 1.0,
+            ..Default::default()
+        };
 
         // draw flow tails
         let mut draw_tail = |pos: Vector2<f64>, dir: Vector2<f64>| {
@@ -152,7 +304,7 @@ pub fn main() {
         for i in 1..(args.width / stride as u32) {
             for j in 1..(args.height / stride as u32) {
                 let pos = Vector2::new(i as f64 * stride, j as f64 * stride);
-                let dir = flow_noise.sample(&pos);
+                let dir = flow.sample(pos);
                 draw_tail(pos, dir);
             }
         }
@@ -162,76 +314,81 @@ pub fn main() {
     // todo: arg gate
     if args.draw_flow_walks {
         let n_walks = args.flow_walk_n;
-        let walk_steps = args.flow_walk_steps;
-        let step_size = args.flow_walk_step_size;
+        let integrator = args.integrator.integrator(args.flow_walk_step_size);
+        let walk_length = args.flow_walk_steps as f64 * args.flow_walk_step_size;
+
+        let gradient = args
+            .palette
+            .gradient(&mut rng)
+            .unwrap_or_else(|e| {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            })
+            .unwrap_or_else(|| {
+                let colors = [
+                    LinSrgb::new(0.00, 0.05, 0.20),
+                    LinSrgb::new(0.70, 0.10, 0.20),
+                    LinSrgb::new(0.95, 0.90, 0.30),
+                ]
+                .map(linear_to_color);
+                PaletteGradient::new(&colors, args.palette.interpolation)
+            });
 
-        let gradient = Gradient::new(vec![
-            LinSrgb::new(0.00, 0.05, 0.20),
-            LinSrgb::new(0.70, 0.10, 0.20),
-            LinSrgb::new(0.95, 0.90, 0.30),
-        ]);
-
-        let taken_colors: Vec<_> = gradient.take(10).collect();
         let color_noise = Simplex::new(rng.gen());
 
-        let mut paint = Paint::default();
-        paint.set_color_rgba8(0, 0, 0, 255);
-        paint.anti_alias = true;
-        let transform = Transform::identity();
-        let mut stroke = Stroke::default();
-        stroke.width = 2.0;
-
-        let mut draw_walk = |pos: &Vector2<f64>, color: Color| {
-            // path
-            let mut pb = PathBuilder::new();
-            pb.move_to(pos.x as f32, pos.y as f32);
-            // cursor
-            let mut x = *pos;
-            for _i in 0..walk_steps {
-                let mut dx = flow_noise.sample(&x);
-                let x2 = x + dx * step_size;
-                dx = flow_noise.sample(&x2);
-                let x3 = x2 + dx * step_size;
-                dx = flow_noise.sample(&x3);
-                let x4 = x3 + dx * step_size;
-                pb.cubic_to(
-                    x2.x as f32,
-                    x2.y as f32,
-                    x3.x as f32,
-                    x3.y as f32,
-                    x4.x as f32,
-                    x4.y as f32,
-                );
-                x = x4;
+        let style = args.ribbon.style();
+        let profile_noise = style.uses_noise().then(|| Simplex::new(rng.gen()));
+        let profile_noise_at = |p: Vector2<f64>| match &profile_noise {
+            Some(noise) => {
+                let p = p * args.ribbon.profile_noise_scale;
+                noise.get([p.x, p.y])
             }
-            let path = pb.finish().unwrap();
-            paint.set_color(color);
-            pixmap.stroke_path(&path, &paint, &stroke, transform, None)
+            None => 0.,
         };
-
-        let x_range = Uniform::new(0., args.width as f64);
-        let y_range = Uniform::new(0., args.height as f64);
-        // let color_range = Uniform::new(0, 10);
-        for _i in (0..n_walks).progress() {
-            let p = Vector2::new(x_range.sample(&mut rng), y_range.sample(&mut rng));
-            let color_scale = args.scale * args.color_scale;
+        let mut draw_walk = |points: &[Vector2<f64>], color: Color| {
+            // speed before normalizing, since walks all move at unit speed
+            let ribbon = style.ribbon(points, |p| raw_flow.sample(p).norm(), profile_noise_at);
+            fill_ribbon(&mut pixmap, &ribbon, color, true, Transform::identity());
+        };
+        let color_at = |p: &Vector2<f64>| {
+            let color_scale = args.color_scale / flow_scale(args);
             let color_range = args.color_range;
             // let color_i = (((color_noise.get([p.x / color_scale, p.y / color_scale]) + 1.) / 2.
             //     * color_range) as usize)
             //     .clamp(0, 9);
-            let color_i = ((color_noise.get([p.x / color_scale, p.y / color_scale]) * color_range)
-                as usize)
-                .clamp(0, 9);
-            // println!("color_i: {}", color_i);
-            let color = taken_colors[color_i];
-            let r = (color.red * 255.) as u8;
-            let g = (color.green * 255.) as u8;
-            let b = (color.blue * 255.) as u8;
-            let skia_color: Color = Color::from_rgba8(r, g, b, 255);
-            draw_walk(&p, skia_color);
+            // color_range is measured in tenths of the gradient
+            let t = color_noise.get([p.x / color_scale, p.y / color_scale]) * color_range / 9.;
+            gradient.lookup(t, args.palette.palette_lookup)
+        };
+
+        // let color_range = Uniform::new(0, 10);
+        let size = [args.width as f64, args.height as f64];
+        let density = density.map(|d| d as &dyn Density);
+        let seeds = args
+            .seeding
+            .seeding()
+            .points(size, n_walks as usize, density, &mut rng);
+        match args.spacing.evenly_spaced() {
+            Some(placement) => {
+                // seeds start lines in regions the others never reach
+                let lines = placement.set_max_length(walk_length).streamlines(
+                    &flow,
+                    &integrator,
+                    size,
+                    seeds,
+                );
+                for line in lines.iter().progress() {
+                    draw_walk(line, color_at(&line[0]));
+                }
+            }
+            None => {
+                for p in seeds.into_iter().progress() {
+                    let points: Vec<_> = integrator.streamline(&flow, p, walk_length).collect();
+                    draw_walk(&points, color_at(&p));
+                }
+            }
         }
     }
 
-    // save result
-    pixmap.save_png(args.out).unwrap();
+    pixmap
 }
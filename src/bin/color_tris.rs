@@ -1,22 +1,43 @@
 //! Draw a grid of triangles, of random colors
 
 use std::f32::consts::PI;
+use std::process;
 
 use bevy::{
     prelude::{
-        default, shape, App, Assets, Camera2dBundle, Color, Commands, Mesh, Quat, Query, ResMut,
-        Transform,
+        default, shape, App, Assets, Camera2dBundle, Color, Commands, Mesh, Quat, Query, Res,
+        ResMut, Resource, Transform,
     },
     sprite::{ColorMaterial, MaterialMesh2dBundle},
     window::Window,
     DefaultPlugins,
 };
-use rand::{distributions::Uniform, prelude::Distribution, SeedableRng};
+use clap::Parser;
+use proc_art::skia_colors::PaletteArgs;
+use rand::{distributions::Uniform, prelude::Distribution, Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 
+/// Draw a grid of triangles, of random colors
+#[derive(Parser, Resource, Debug)]
+#[command(author, version, about, long_about=None)]
+#[command(mut_arg("palette", |arg| arg.help(
+    "bundled palette name, palette file or generator (gen:triadic).  Colors are fully random if omitted."
+)))]
+struct Args {
+    /// random seed for colors and generated palettes
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[command(flatten)]
+    palette: PaletteArgs,
+}
+
 fn main() {
+    let args = Args::parse();
+
     App::new()
         .add_plugins(DefaultPlugins)
+        .insert_resource(args)
         .add_startup_system(setup)
         .add_system(bevy::window::close_on_esc)
         .run();
@@ -27,6 +48,7 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     window: Query<&Window>,
+    args: Res<Args>,
 ) {
     // camera
     commands.spawn(Camera2dBundle::default());
@@ -44,8 +66,26 @@ fn setup(
     let jw = (window_h / triangle_height / 2.) as i32 + 1;
 
     // triangles strip
-    let mut rng = ChaCha8Rng::from_entropy();
+    let mut rng = match args.seed {
+        Some(n) => ChaCha8Rng::seed_from_u64(n),
+        None => ChaCha8Rng::from_entropy(),
+    };
     let color_value_dist = Uniform::new(0., 1.);
+    let gradient = args.palette.gradient(&mut rng).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+    let random_color = |rng: &mut ChaCha8Rng| match &gradient {
+        Some(gradient) => {
+            let c = gradient.lookup(rng.gen(), args.palette.palette_lookup);
+            Color::rgb(c.red(), c.green(), c.blue())
+        }
+        None => Color::rgb(
+            color_value_dist.sample(rng),
+            color_value_dist.sample(rng),
+            color_value_dist.sample(rng),
+        ),
+    };
     for i in -iw..iw {
         for j in -jw..jw {
             let mut x = i as f32 * triangle_side;
@@ -54,11 +94,7 @@ fn setup(
             }
             let mut y = j as f32 * (triangle_radius + triangle_short_radius);
 
-            let color = Color::rgb(
-                color_value_dist.sample(&mut rng),
-                color_value_dist.sample(&mut rng),
-                color_value_dist.sample(&mut rng),
-            );
+            let color = random_color(&mut rng);
             commands.spawn(MaterialMesh2dBundle {
                 mesh: meshes
                     .add(shape::RegularPolygon::new(triangle_radius, 3).into())
@@ -70,11 +106,7 @@ fn setup(
 
             x += 2. * triangle_short_radius * (PI / 6.).cos();
             y += 2. * triangle_short_radius * (PI / 6.).sin();
-            let color = Color::rgb(
-                color_value_dist.sample(&mut rng),
-                color_value_dist.sample(&mut rng),
-                color_value_dist.sample(&mut rng),
-            );
+            let color = random_color(&mut rng);
             commands.spawn(MaterialMesh2dBundle {
                 mesh: meshes
                     .add(shape::RegularPolygon::new(triangle_radius, 3).into())
@@ -0,0 +1,62 @@
+//! Extract a palette from a reference image and write it in the `.hex` format
+//! used by `assets/colors`.
+
+use std::{fs, process};
+
+use clap::Parser;
+use proc_art::{
+    palette_extract::{extract_palette_from_file, ExtractOptions, QuantizeMethod},
+    skia_colors::to_hex_palette,
+};
+
+/// Extract a palette from a reference image in the .hex format
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about=None)]
+struct Args {
+    /// reference image
+    image: String,
+
+    /// output path.  The palette is printed to stdout if omitted.
+    #[arg(short, long)]
+    out: Option<String>,
+
+    /// number of colors to extract
+    #[arg(short = 'n', long, default_value_t = 8)]
+    colors: usize,
+
+    #[arg(long, value_enum, default_value_t = QuantizeMethod::KMeans)]
+    method: QuantizeMethod,
+
+    /// maximum number of pixels sampled from the image
+    #[arg(long, default_value_t = 100_000)]
+    max_samples: usize,
+}
+
+fn main() {
+    let args = Args::parse();
+    let opts = ExtractOptions {
+        n_colors: args.colors,
+        method: args.method,
+        max_samples: args.max_samples,
+        ..Default::default()
+    };
+
+    let colors = match extract_palette_from_file(&args.image, &opts) {
+        Ok(colors) => colors,
+        Err(e) => {
+            eprintln!("error: could not read {}: {}", args.image, e);
+            process::exit(1);
+        }
+    };
+
+    let hex = to_hex_palette(&colors);
+    match args.out {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, hex) {
+                eprintln!("error: could not write {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", hex),
+    }
+}
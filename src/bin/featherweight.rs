@@ -1,13 +1,27 @@
+use std::process;
+
 use clap::Parser;
 use image::{Rgb, RgbImage};
-use noise::{NoiseFn, Perlin};
-use rand::distributions::{Distribution, Uniform};
+use nalgebra::Vector2;
+use proc_art::{
+    flow::{EvenSpacingArgs, FlowArgs, FlowField, IntegratorArgs, Normalize, ScalePosition},
+    noise::{frame_path, AnimationArgs, NoiseArgs},
+    points::{Density, SeedingArgs},
+    skia_colors::{color_to_rgb, PaletteArgs},
+};
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
 
 /// Program to illustrate perlin noise flow
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about=None)]
+#[command(mut_arg("noise_type", |arg| arg.default_value("perlin")))]
+#[command(mut_arg("noise_scale", |arg| arg.default_value("10").alias("scale")))]
+#[command(mut_arg("noise_norm", |arg| arg.default_value("true")))]
+#[command(mut_arg("integrator", |arg| arg.default_value("euler")))]
+#[command(mut_arg("palette", |arg| arg.help(
+    "bundled palette name, palette file or generator (gen:triadic) to pick walk colors from.  Walks are white if omitted."
+)))]
 struct Args {
     /// output path
     #[arg(short, long, default_value_t = String::from("featherweight.png"))]
@@ -16,9 +30,6 @@ struct Args {
     #[arg(long, default_value_t = 1024)]
     size: u32,
 
-    #[arg(long, default_value_t = 10.)]
-    scale: f64,
-
     #[arg(long)]
     seed: Option<u64>,
 
@@ -40,6 +51,7 @@ struct Args {
     #[arg(long, default_value_t = true)]
     draw_flow_walks: bool,
 
+    /// walks to draw, or roughly how many with poisson and grid seeding
     #[arg(long, default_value_t = 1000)]
     flow_walk_freq: u32,
 
@@ -48,34 +60,106 @@ struct Args {
 
     #[arg(long)]
     flow_walk_norm: bool,
+
+    /// time per walk step, in pixels at unit flow speed
+    #[arg(long, default_value_t = 1.)]
+    flow_walk_step_size: f64,
+
+    #[command(flatten)]
+    integrator: IntegratorArgs,
+
+    #[command(flatten)]
+    seeding: SeedingArgs,
+
+    #[command(flatten)]
+    spacing: EvenSpacingArgs,
+
+    #[command(flatten)]
+    flow: FlowArgs,
+
+    #[command(flatten)]
+    palette: PaletteArgs,
+
+    #[command(flatten)]
+    noise: NoiseArgs,
+
+    #[command(flatten)]
+    animation: AnimationArgs,
 }
 
 pub fn main() {
     // parse args
     let args = Args::parse();
 
-    // create image buffer
-    let mut img = RgbImage::new(args.size, args.size);
-
     // generate flow directions from perlin noise
     // todo(axelmagn): seed from arg
     let mut rng = match args.seed {
         Some(n) => ChaCha8Rng::seed_from_u64(n),
         _ => ChaCha8Rng::from_entropy(),
     };
-    let flow_x = Perlin::new(rng.gen());
-    let flow_y = Perlin::new(rng.gen());
+    let [seed_x, seed_y] = args.noise.seeds(&mut rng);
+    let get_field = |frame| {
+        let build = |seed| {
+            args.noise.build_unscaled(seed, frame).unwrap_or_else(|e| {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            })
+        };
+        args.flow.build(build(seed_x), build(seed_y))
+    };
+    let size = [args.size as f64; 2];
+    let density = args
+        .seeding
+        .density(size, || {
+            // the rng only advances when noise density is asked for
+            let [.., seed] = args.noise.seeds::<3>(&mut rng);
+            args.noise.build(seed, size, None).unwrap_or_else(|e| {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            })
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("error: could not load seed density: {}", e);
+            process::exit(1);
+        });
+
+    match args.animation.animation() {
+        Some(animation) => {
+            // every frame starts from the same rng state, so walks start in the same places
+            for (i, time) in animation.times().enumerate() {
+                let field = get_field(Some((&animation, time)));
+                let img = draw(&args, rng.clone(), field, density.as_deref());
+                img.save(frame_path(&args.out, i)).unwrap();
+            }
+        }
+        None => {
+            let field = get_field(None);
+            let img = draw(&args, rng, field, density.as_deref());
+            img.save(&args.out).unwrap();
+        }
+    }
+}
+
+fn draw(
+    args: &Args,
+    mut rng: ChaCha8Rng,
+    field: Box<dyn FlowField + Send + Sync>,
+    density: Option<&(dyn Density + Send + Sync)>,
+) -> RgbImage {
+    // create image buffer
+    let mut img = RgbImage::new(args.size, args.size);
+    // flow over pixels, and at unit speed for normalized walks
+    let flow = ScalePosition::new(field, args.noise.scale([args.size as f64; 2]));
+    let unit_flow = Normalize::new(&flow);
+    let in_bounds = |pos: Vector2<f64>| pos.iter().all(|v| (0. ..args.size as f64).contains(v));
 
     // draw flow background
     if args.draw_flow_bg {
         for x in 0..args.size {
             for y in 0..args.size {
-                let fx = x as f64 / args.size as f64 * args.scale;
-                let fy = y as f64 / args.size as f64 * args.scale;
-                let red = flow_x.get([fx, fy]);
-                let red = ((red + 1.) / 2. * 255.) as u8;
-                let blue = flow_y.get([fx, fy]);
-                let blue = ((blue + 1.) / 2. * 255.) as u8;
+                let v = flow.sample(Vector2::new(x as f64, y as f64));
+                let red = ((v.x + 1.) / 2. * 255.) as u8;
+                let blue = ((v.y + 1.) / 2. * 255.) as u8;
                 img.put_pixel(x, y, Rgb([red, blue, 0]));
             }
         }
@@ -87,64 +171,76 @@ pub fn main() {
         let tail_len = args.flow_tail_length;
         for x in (0..args.size).step_by(stride) {
             for y in (0..args.size).step_by(stride) {
-                // floating point coords
-                let mut fx = x as f64;
-                let mut fy = y as f64;
-                // noise coords
-                let nx = fx / args.size as f64 * args.scale;
-                let ny = fy / args.size as f64 * args.scale;
+                let mut pos = Vector2::new(x as f64, y as f64);
                 // get flow vector at this point
-                let vx = flow_x.get([nx, ny]);
-                let vy = flow_y.get([nx, ny]);
-                let red = ((vx + 1.) / 2. * 255.) as u8;
-                let blue = ((vy + 1.) / 2. * 255.) as u8;
+                let v = flow.sample(pos);
+                let red = ((v.x + 1.) / 2. * 255.) as u8;
+                let blue = ((v.y + 1.) / 2. * 255.) as u8;
                 let tail_color = Rgb([red, blue, 0]);
                 for _i in 0..tail_len {
-                    if fx < 0. || fx < 0. || fx >= args.size as f64 || fy >= args.size as f64 {
+                    if !in_bounds(pos) {
                         break;
                     }
-                    img.put_pixel(fx as u32, fy as u32, tail_color);
-                    fx += vx;
-                    fy += vy;
+                    img.put_pixel(pos.x as u32, pos.y as u32, tail_color);
+                    pos += v;
                 }
             }
         }
     }
 
     if args.draw_flow_walks {
-        let walk_color = Rgb([255, 255, 255]);
-        let walk_len = args.flow_walk_length;
-        let between = Uniform::new(0., args.size as f64);
-        for _i in 0..args.flow_walk_freq {
-            // floating point coords
-            let mut fx: f64 = between.sample(&mut rng);
-            let mut fy: f64 = between.sample(&mut rng);
-            for _i in 0..walk_len {
-                if fx < 0. || fx < 0. || fx >= args.size as f64 || fy >= args.size as f64 {
-                    break;
+        let gradient = args.palette.gradient(&mut rng).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        });
+        let random_color = |rng: &mut ChaCha8Rng| match &gradient {
+            Some(gradient) => {
+                color_to_rgb(&gradient.lookup(rng.gen(), args.palette.palette_lookup))
+            }
+            None => Rgb([255, 255, 255]),
+        };
+        let integrator = args.integrator.integrator(args.flow_walk_step_size);
+        let walk_duration = args.flow_walk_length as f64 * args.flow_walk_step_size;
+        let walk_flow: &dyn FlowField = if args.flow_walk_norm {
+            &unit_flow
+        } else {
+            &flow
+        };
+        let size = [args.size as f64; 2];
+        let density = density.map(|d| d as &dyn Density);
+        let seeds =
+            args.seeding
+                .seeding()
+                .points(size, args.flow_walk_freq as usize, density, &mut rng);
+        match args.spacing.evenly_spaced() {
+            Some(placement) => {
+                // seeds start lines in regions the others never reach
+                let lines = placement.set_max_length(walk_duration).streamlines(
+                    walk_flow,
+                    &integrator,
+                    size,
+                    seeds,
+                );
+                for line in lines {
+                    let walk_color = random_color(&mut rng);
+                    for pos in line.into_iter().filter(|pos| in_bounds(*pos)) {
+                        img.put_pixel(pos.x as u32, pos.y as u32, walk_color);
+                    }
                 }
-                img.put_pixel(fx as u32, fy as u32, walk_color);
-
-                // noise coords
-                let nx = fx / args.size as f64 * args.scale;
-                let ny = fy / args.size as f64 * args.scale;
-                // get flow vector at this point
-                let mut vx = flow_x.get([nx, ny]);
-                let mut vy = flow_y.get([nx, ny]);
-                // normalize velocity (optional)
-                if args.flow_walk_norm {
-                    let norm = (vx * vx + vy * vy).sqrt();
-                    if norm > 0. {
-                        vx /= norm;
-                        vy /= norm;
+            }
+            None => {
+                for start in seeds {
+                    let walk_color = random_color(&mut rng);
+                    for pos in integrator
+                        .streamline(walk_flow, start, walk_duration)
+                        .take_while(|pos| in_bounds(*pos))
+                    {
+                        img.put_pixel(pos.x as u32, pos.y as u32, walk_color);
                     }
                 }
-                fx += vx;
-                fy += vy;
             }
         }
     }
 
-    // save image
-    img.save(args.out).unwrap();
+    img
 }
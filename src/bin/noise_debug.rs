@@ -1,14 +1,26 @@
 //! Draw the outputs of a noise function for debugging
 
-use clap::{Parser, ValueEnum};
-use indicatif::ProgressIterator;
-use noise::{NoiseFn, Perlin, ScalePoint, Simplex};
-use rand::{thread_rng, Rng};
+use std::process;
+
+use clap::Parser;
+use noise::NoiseFn;
+use proc_art::{
+    noise::{frame_path, Animation, AnimationArgs, LoadNoiseConfigError, NoiseArgs, NormalizeArgs},
+    raster::par_paint_noise,
+    skia_colors::{color_to_pixel, PaletteArgs, PaletteGradient},
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use tiny_skia::{Pixmap, PremultipliedColorU8};
 
+/// Draw the outputs of a noise function for debugging
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
+#[command(mut_arg("palette", |arg| arg.help(
+    "color noise values with a bundled palette name, palette file or generator (gen:triadic) instead of grayscale"
+)))]
 struct Args {
+    /// output path
     #[arg(short, long, default_value_t = String::from("noise_debug.png"))]
     out: String,
 
@@ -20,52 +32,99 @@ struct Args {
     #[arg(long, default_value_t = 600)]
     height: u32,
 
-    #[arg(long, value_enum, default_value_t = NoiseType::Simplex)]
-    noise_type: NoiseType,
+    /// random seed, for the noise and generated palettes
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// make the image tile seamlessly
+    #[arg(long, conflicts_with = "frames")]
+    tileable: bool,
 
-    #[arg(long, default_value_t = 1.)]
-    noise_scale: f64,
+    #[command(flatten)]
+    noise: NoiseArgs,
 
-    /// normalize noise scale to size of image
-    #[arg(long)]
-    noise_norm: bool,
+    #[command(flatten)]
+    normalize: NormalizeArgs,
+
+    #[command(flatten)]
+    animation: AnimationArgs,
+
+    #[command(flatten)]
+    palette: PaletteArgs,
 }
 
 impl Args {
-    fn get_noise_fn(&self, seed: u32) -> Box<dyn NoiseFn<f64, 2>> {
-        let noise: Box<dyn NoiseFn<f64, 2>> = match self.noise_type {
-            NoiseType::Perlin => Box::new(Perlin::new(seed)),
-            NoiseType::Simplex => Box::new(Simplex::new(seed)),
-        };
-        let mut scale = self.noise_scale;
-        if self.noise_norm {
-            scale /= self.width.max(self.height) as f64;
+    /// The noise to draw before normalizing, or the noise for one frame if `frame` is given.
+    fn get_noise_fn(
+        &self,
+        seed: u32,
+        frame: Option<(&Animation, f64)>,
+    ) -> Result<Box<dyn NoiseFn<f64, 2> + Send + Sync>, LoadNoiseConfigError> {
+        let size = self.size();
+        if self.tileable {
+            self.noise.build_tileable(seed, size)
+        } else {
+            self.noise.build(seed, size, frame)
         }
-        let noise = ScalePoint::new(noise).set_scale(scale);
-        Box::new(noise)
     }
-}
 
-#[derive(Debug, Clone, ValueEnum)]
-enum NoiseType {
-    Simplex,
-    Perlin,
+    fn size(&self) -> [f64; 2] {
+        [self.width as f64, self.height as f64]
+    }
 }
 
 pub fn main() {
     let args = Args::parse();
-    let mut rng = thread_rng();
-    let noise = args.get_noise_fn(rng.gen());
-    let mut pixmap = Pixmap::new(args.width, args.height).unwrap();
-    let pixels = pixmap.pixels_mut();
-
-    for i in (0..(args.width * args.height)).progress() {
-        let x = i % args.width;
-        let y = i / args.width;
-        let v = noise.get([x as f64, y as f64]);
-        let rgb = ((v + 1.) / 2. * 256.).clamp(0., 255.) as u8;
-        pixels[i as usize] = PremultipliedColorU8::from_rgba(rgb, rgb, rgb, 255).unwrap();
+    let mut rng = match args.seed {
+        Some(n) => ChaCha8Rng::seed_from_u64(n),
+        None => ChaCha8Rng::from_entropy(),
+    };
+    let [seed] = args.noise.seeds(&mut rng);
+    let gradient = args.palette.gradient(&mut rng).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+    let get_noise_fn = |frame| {
+        args.get_noise_fn(seed, frame).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        })
+    };
+
+    match args.animation.animation() {
+        Some(animation) => {
+            let frames: Vec<_> = animation
+                .times()
+                .map(|time| get_noise_fn(Some((&animation, time))))
+                .collect();
+            // normalize every frame the same way, so the animation doesn't flicker
+            let stats = args.normalize.sample(&frames, args.size());
+            for (i, noise) in frames.into_iter().enumerate() {
+                let noise = args.normalize.apply_stats(noise, stats.as_ref());
+                let pixmap = draw(&args, noise.as_ref(), gradient.as_ref());
+                pixmap.save_png(frame_path(&args.out, i)).unwrap();
+            }
+        }
+        None => {
+            let noise = args.normalize.apply(get_noise_fn(None), args.size());
+            let pixmap = draw(&args, noise.as_ref(), gradient.as_ref());
+            pixmap.save_png(&args.out).unwrap();
+        }
     }
+}
 
-    pixmap.save_png("noise_debug.png").unwrap();
+fn draw(
+    args: &Args,
+    noise: &(dyn NoiseFn<f64, 2> + Send + Sync),
+    gradient: Option<&PaletteGradient>,
+) -> Pixmap {
+    par_paint_noise(noise, args.width, args.height, |v| match gradient {
+        Some(gradient) => {
+            color_to_pixel(&gradient.lookup((v + 1.) / 2., args.palette.palette_lookup))
+        }
+        None => {
+            let rgb = ((v + 1.) / 2. * 256.).clamp(0., 255.) as u8;
+            PremultipliedColorU8::from_rgba(rgb, rgb, rgb, 255).unwrap()
+        }
+    })
 }
@@ -1,16 +1,21 @@
 //! draw a grid of triangles, with colors derived from a noise function
 
-use std::{fs, num::ParseIntError};
-
-use clap::{Parser, ValueEnum};
-use noise::{NoiseFn, Perlin, ScalePoint, Simplex};
-use rand::{thread_rng, Rng};
-use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Point, Transform};
-
-const DEFAULT_PALETTE: &'static str = include_str!("../../assets/colors/ocaso.hex");
-
+use std::process;
+
+use clap::Parser;
+use noise::NoiseFn;
+use proc_art::{
+    noise::{frame_path, Animation, AnimationArgs, LoadNoiseConfigError, NoiseArgs, NormalizeArgs},
+    skia_colors::{PaletteArgs, PaletteGradient, DEFAULT_PALETTE_NAME},
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Point, Transform};
+
+/// Draw a grid of triangles, with colors derived from a noise function
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
+#[command(mut_arg("palette", |arg| arg.default_value(DEFAULT_PALETTE_NAME).alias("palette-file")))]
 struct Args {
     /// output path
     #[arg(short, long, default_value_t = String::from("noise_tris.png"))]
@@ -24,56 +29,92 @@ struct Args {
     #[arg(long, default_value_t = 600)]
     height: u32,
 
+    /// random seed, for the noise and generated palettes
+    #[arg(long)]
+    seed: Option<u64>,
+
     #[arg(long, default_value_t = 32.)]
     triangle_size: f32,
 
-    #[arg(long)]
-    palette_file: Option<String>,
+    #[command(flatten)]
+    palette: PaletteArgs,
 
-    #[arg(long, value_enum, default_value_t = NoiseType::Simplex)]
-    noise_type: NoiseType,
+    /// make the image tile seamlessly
+    #[arg(long, conflicts_with = "frames")]
+    tileable: bool,
 
-    #[arg(long, default_value_t = 1.)]
-    noise_scale: f64,
+    #[command(flatten)]
+    noise: NoiseArgs,
 
-    /// normalize noise scale to size of image
-    #[arg(long)]
-    noise_norm: bool,
-}
+    #[command(flatten)]
+    normalize: NormalizeArgs,
 
-#[derive(Debug, Clone, ValueEnum)]
-enum NoiseType {
-    Simplex,
-    Perlin,
+    #[command(flatten)]
+    animation: AnimationArgs,
 }
 
 impl Args {
-    fn load_palette(&self) -> Result<Vec<Color>, ParseHexColorError> {
-        let contents = match &self.palette_file {
-            Some(path) => fs::read_to_string(path).expect("could not read palette file"),
-            None => String::from(DEFAULT_PALETTE),
-        };
-        parse_hex_palette(&contents)
+    /// The height noise before normalizing, or the height noise for one frame if `frame`
+    /// is given.
+    fn get_height_fn(
+        &self,
+        seed: u32,
+        frame: Option<(&Animation, f64)>,
+    ) -> Result<Box<dyn NoiseFn<f64, 2> + Send + Sync>, LoadNoiseConfigError> {
+        let size = self.size();
+        if self.tileable {
+            self.noise.build_tileable(seed, size)
+        } else {
+            self.noise.build(seed, size, frame)
+        }
     }
 
-    fn get_height_fn(&self, seed: u32) -> Box<dyn NoiseFn<f64, 2>> {
-        let noise: Box<dyn NoiseFn<f64, 2>> = match self.noise_type {
-            NoiseType::Perlin => Box::new(Perlin::new(seed)),
-            NoiseType::Simplex => Box::new(Simplex::new(seed)),
-        };
-        let mut scale = self.noise_scale;
-        if self.noise_norm {
-            scale /= self.width.max(self.height) as f64;
-        }
-        let noise = ScalePoint::new(noise).set_scale(scale);
-        Box::new(noise)
+    fn size(&self) -> [f64; 2] {
+        [self.width as f64, self.height as f64]
     }
 }
 
 fn main() {
     let args = Args::parse();
-    let pixmap = paint_main(&args);
-    pixmap.save_png(args.out).unwrap();
+    let mut rng = match args.seed {
+        Some(n) => ChaCha8Rng::seed_from_u64(n),
+        None => ChaCha8Rng::from_entropy(),
+    };
+    let [seed] = args.noise.seeds(&mut rng);
+    let palette = args
+        .palette
+        .gradient(&mut rng)
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        })
+        .expect("--palette has a default");
+    let get_height_fn = |frame| {
+        args.get_height_fn(seed, frame).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        })
+    };
+    match args.animation.animation() {
+        Some(animation) => {
+            let frames: Vec<_> = animation
+                .times()
+                .map(|time| get_height_fn(Some((&animation, time))))
+                .collect();
+            // normalize every frame the same way, so the animation doesn't flicker
+            let stats = args.normalize.sample(&frames, args.size());
+            for (i, height) in frames.into_iter().enumerate() {
+                let height = args.normalize.apply_stats(height, stats.as_ref());
+                let pixmap = paint_main(&args, &palette, height);
+                pixmap.save_png(frame_path(&args.out, i)).unwrap();
+            }
+        }
+        None => {
+            let height = args.normalize.apply(get_height_fn(None), args.size());
+            let pixmap = paint_main(&args, &palette, height);
+            pixmap.save_png(&args.out).unwrap();
+        }
+    }
 }
 
 // struct PaintTask {}
@@ -84,20 +125,29 @@ struct NoiseData {
     // TODO: flow
     // flow_x: Box<dyn NoiseFn<f32, 2>>,
     // flow_y: Box<dyn NoiseFn<f32, 2>>,
-    height: Box<dyn NoiseFn<f64, 2>>,
+    height: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
 }
 
-fn paint_main(args: &Args) -> Pixmap {
-    let triangle_side = args.triangle_size;
+fn paint_main(
+    args: &Args,
+    palette: &PaletteGradient,
+    height: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+) -> Pixmap {
+    let mut triangle_side = args.triangle_size;
+    let mut triangle_height = triangle_side * (60_f32).to_radians().sin();
+    if args.tileable {
+        // stretch the grid slightly to fit whole columns and pairs of rows, so it tiles too
+        let columns = (args.width as f32 / triangle_side).round().max(1.);
+        let row_pairs = (args.height as f32 / (2. * triangle_height))
+            .round()
+            .max(1.);
+        triangle_side = args.width as f32 / columns;
+        triangle_height = args.height as f32 / (2. * row_pairs);
+    }
     let triangle_half_side = triangle_side / 2.;
-    let triangle_height = triangle_side * (60_f32).to_radians().sin();
     let triangle_half_height = triangle_height / 2.;
-    let palette = args.load_palette().expect("could not load palette");
 
-    let mut rng = thread_rng();
-    let noise_data = NoiseData {
-        height: args.get_height_fn(rng.gen()),
-    };
+    let noise_data = NoiseData { height };
 
     let mut pixmap = Pixmap::new(args.width, args.height).unwrap();
 
@@ -112,33 +162,38 @@ fn paint_main(args: &Args) -> Pixmap {
             let y = j as f32 * triangle_height;
             let pos = Point::from_xy(x, y);
 
-            let mut paint = Paint::default();
-            paint.anti_alias = true;
+            let mut paint = Paint {
+                anti_alias: true,
+                ..Default::default()
+            };
 
             let sample_x = (x + triangle_half_side) as f64;
             let sample_y = (y + triangle_half_height) as f64;
-            let height = (noise_data.height.get([sample_x, sample_y]) + 1.) / 2.
-                * (palette.len() - 1) as f64;
-            let color = palette[height as usize];
+            let height = (noise_data.height.get([sample_x, sample_y]) + 1.) / 2.;
+            let color = palette.lookup(height, args.palette.palette_lookup);
             paint.set_color(color);
-            draw_top_triangle(pos, triangle_side, &paint, &mut pixmap);
+            draw_top_triangle(pos, triangle_side, triangle_height, &paint, &mut pixmap);
 
             let sample_x = x as f64;
             let sample_y = (y + triangle_half_height) as f64;
-            let height = (noise_data.height.get([sample_x, sample_y]) + 1.) / 2.
-                * (palette.len() - 1) as f64;
-            let color = palette[height as usize];
+            let height = (noise_data.height.get([sample_x, sample_y]) + 1.) / 2.;
+            let color = palette.lookup(height, args.palette.palette_lookup);
             paint.set_color(color);
-            draw_bottom_triangle(pos, triangle_side, &paint, &mut pixmap);
+            draw_bottom_triangle(pos, triangle_side, triangle_height, &paint, &mut pixmap);
         }
     }
 
     pixmap
 }
 
-fn draw_top_triangle(pos: Point, triangle_side: f32, paint: &Paint, pixmap: &mut Pixmap) {
+fn draw_top_triangle(
+    pos: Point,
+    triangle_side: f32,
+    triangle_height: f32,
+    paint: &Paint,
+    pixmap: &mut Pixmap,
+) {
     let triangle_half_side = triangle_side / 2.;
-    let triangle_height = triangle_side * (60_f32).to_radians().sin();
     let points = [
         pos,
         Point::from_xy(pos.x + triangle_side, pos.y),
@@ -147,9 +202,14 @@ fn draw_top_triangle(pos: Point, triangle_side: f32, paint: &Paint, pixmap: &mut
     draw_triangle(&points, paint, pixmap)
 }
 
-fn draw_bottom_triangle(pos: Point, triangle_side: f32, paint: &Paint, pixmap: &mut Pixmap) {
+fn draw_bottom_triangle(
+    pos: Point,
+    triangle_side: f32,
+    triangle_height: f32,
+    paint: &Paint,
+    pixmap: &mut Pixmap,
+) {
     let triangle_half_side = triangle_side / 2.;
-    let triangle_height = triangle_side * (60_f32).to_radians().sin();
     let points = [
         pos,
         Point::from_xy(pos.x + triangle_half_side, pos.y + triangle_height),
@@ -165,82 +225,5 @@ fn draw_triangle(points: &[Point; 3], paint: &Paint, pixmap: &mut Pixmap) {
     pb.line_to(points[2].x, points[2].y);
     pb.close();
     let path = pb.finish().unwrap();
-    pixmap.fill_path(
-        &path,
-        &paint,
-        FillRule::Winding,
-        Transform::identity(),
-        None,
-    );
-}
-
-#[derive(Debug, PartialEq)]
-enum ParseHexColorError {
-    WrongColorStringLength {
-        input_str: String,
-        actual_length: usize,
-        expected_length: usize,
-    },
-    ParseIntError(ParseIntError),
-}
-
-impl From<ParseIntError> for ParseHexColorError {
-    fn from(value: ParseIntError) -> Self {
-        ParseHexColorError::ParseIntError(value)
-    }
-}
-
-fn parse_hex_palette(s: &str) -> Result<Vec<Color>, ParseHexColorError> {
-    s.lines().map(parse_hex_color).collect()
-}
-
-fn parse_hex_color(s: &str) -> Result<Color, ParseHexColorError> {
-    if s.len() != 6 {
-        return Err(ParseHexColorError::WrongColorStringLength {
-            input_str: String::from(s),
-            actual_length: s.len(),
-            expected_length: 6,
-        });
-    }
-
-    let r = u8::from_str_radix(&s[0..2], 16)?;
-    let g = u8::from_str_radix(&s[2..4], 16)?;
-    let b = u8::from_str_radix(&s[4..6], 16)?;
-    Ok(Color::from_rgba8(r, g, b, 255))
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_parse_hex_palette() {
-        let palette_str = r"000000
-ff0000
-00ff00
-0000ff
-ffffff";
-
-        let expected_colors = vec![
-            Color::from_rgba8(0, 0, 0, 255),
-            Color::from_rgba8(255, 0, 0, 255),
-            Color::from_rgba8(0, 255, 0, 255),
-            Color::from_rgba8(0, 0, 255, 255),
-            Color::from_rgba8(255, 255, 255, 255),
-        ];
-
-        assert_eq!(parse_hex_palette(palette_str), Ok(expected_colors));
-    }
-
-    #[test]
-    fn test_parse_hex_color() {
-        assert_eq!(
-            parse_hex_color("000000"),
-            Ok(Color::from_rgba8(0, 0, 0, 255))
-        );
-        assert_eq!(
-            parse_hex_color("FF0000"),
-            Ok(Color::from_rgba8(255, 0, 0, 255))
-        );
-    }
+    pixmap.fill_path(&path, paint, FillRule::Winding, Transform::identity(), None);
 }
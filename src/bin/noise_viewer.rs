@@ -5,6 +5,8 @@
 //! --------
 //! Space: generate new random image
 
+use std::process;
+
 use bevy::{
     prelude::{
         default, App, Assets, Camera2dBundle, Commands, EventReader, EventWriter, Handle, Image,
@@ -14,38 +16,42 @@ use bevy::{
     window::Window,
     DefaultPlugins,
 };
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use image::{DynamicImage, RgbaImage};
-use indicatif::ProgressIterator;
 use log::info;
-use noise::{NoiseFn, ScalePoint};
-use palette::{
-    encoding::{Linear, Srgb},
-    rgb::Rgb,
-    Gradient, LinSrgb,
+use noise::NoiseFn;
+use proc_art::{
+    noise::NoiseArgs,
+    raster::par_paint_noise,
+    skia_colors::{
+        color_to_pixel, Harmony, PaletteArgs, PaletteGradient, PaletteLookup, PaletteSpec,
+        DEFAULT_HARMONY_COLORS,
+    },
 };
-use proc_art::noise::NoiseSelector;
 use rand::{distributions::Uniform, thread_rng, Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
 use tiny_skia::{
-    Color as SkiaColor, FillRule, Paint, PathBuilder, Pixmap, PremultipliedColorU8,
-    Transform as SkiaTransform,
+    Color as SkiaColor, FillRule, Paint, PathBuilder, Pixmap, Transform as SkiaTransform,
 };
 
+/// View the output of a noise function in realtime
 #[derive(Parser, Resource, Debug)]
 #[command(author, version, about, long_about=None)]
+#[command(mut_arg("noise_type", |arg| arg.default_value("perlin")))]
+#[command(mut_arg("noise_scale", |arg| arg.default_value("4")))]
+#[command(mut_arg("noise_norm", |arg| arg.default_value("true")))]
+#[command(mut_arg("palette", |arg| arg.help(
+    "bundled palette name, palette file or generator (gen:triadic).  A random harmony is generated if omitted."
+)))]
+#[command(mut_arg("palette_lookup", |arg| arg.default_value("continuous")))]
 struct Args {
     /// initial random seed
     #[arg(long)]
     seed: Option<u64>,
 
-    /// type of random noise
-    #[arg(long, value_enum, default_value_t = NoiseSelector::Perlin)]
-    noise_type: NoiseSelector,
-
-    /// noise scale
-    #[arg(long, default_value_t = 4.)]
-    noise_scale: f64,
+    // Tab cycles --noise-type underneath the other noise options.
+    #[command(flatten)]
+    noise: NoiseArgs,
 
     /// window width
     #[arg(long, default_value_t = 800.)]
@@ -54,6 +60,9 @@ struct Args {
     /// window height
     #[arg(long, default_value_t = 600.)]
     height: f64,
+
+    #[command(flatten)]
+    palette: PaletteArgs,
 }
 
 impl Args {
@@ -62,11 +71,10 @@ impl Args {
         seed: u32,
         window_width: u32,
         window_height: u32,
-    ) -> Box<dyn NoiseFn<f64, 2>> {
-        let noise_fn = self.noise_type.get_noise_2d(seed);
-        let scale = self.noise_scale / window_width.max(window_height) as f64;
-        let noise_fn = ScalePoint::new(noise_fn).set_scale(scale);
-        Box::new(noise_fn)
+    ) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        self.noise
+            .build(seed, [window_width as f64, window_height as f64], None)
+            .expect("noise files are loaded at startup")
     }
 
     fn get_seed(&self) -> u64 {
@@ -80,6 +88,13 @@ impl Args {
 #[derive(Resource, Default, Debug)]
 struct DisplayImage(Handle<Image>);
 
+/// Resource containing the gradient the noise is painted with, resolved once at startup.
+#[derive(Resource, Debug)]
+struct DisplayGradient {
+    spec: PaletteSpec,
+    gradient: PaletteGradient,
+}
+
 /// Resource containing the current random seed.  This is different from the seed provided in Args, which is just the initial seed provided to the system.
 #[derive(Resource, Default, Debug)]
 struct RandomSeed(u64);
@@ -87,18 +102,44 @@ struct RandomSeed(u64);
 enum ResourceUpdatedEvent {
     Args,
     RandomSeed,
-    WindowSize,
 }
 
 fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    // read noise files once, not on every redraw
+    if let Err(e) = args.noise.config.load_files() {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
     let seed = RandomSeed(args.get_seed());
 
+    // resolve the palette once; without one, pick a random harmony from the initial seed
+    let mut rng = ChaChaRng::seed_from_u64(seed.0);
+    let mut palette = args.palette.clone();
+    let spec = palette
+        .palette
+        .get_or_insert_with(|| {
+            let harmonies = Harmony::value_variants();
+            PaletteSpec::Generated {
+                harmony: harmonies[rng.gen_range(0..harmonies.len())],
+                n_colors: DEFAULT_HARMONY_COLORS,
+            }
+        })
+        .clone();
+    let gradient = palette
+        .gradient(&mut rng)
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        })
+        .expect("a palette is always given");
+
     App::new()
         .add_plugins(DefaultPlugins)
         .add_event::<ResourceUpdatedEvent>()
         .insert_resource(args)
         .insert_resource(seed)
+        .insert_resource(DisplayGradient { spec, gradient })
         .init_resource::<DisplayImage>()
         .add_startup_system(bevy_setup)
         .add_system(handle_input)
@@ -110,8 +151,12 @@ fn bevy_setup(
     mut commands: Commands,
     mut images: ResMut<Assets<Image>>,
     mut display_img: ResMut<DisplayImage>,
+    gradient: Res<DisplayGradient>,
     window: Query<&Window>,
 ) {
+    // the logger isn't set up until the app runs, so report the palette from here
+    info!("palette: {}", gradient.spec);
+
     // create camera
     commands.spawn(Camera2dBundle::default());
 
@@ -150,11 +195,11 @@ fn handle_input(
 
     if keys.just_pressed(KeyCode::Tab) {
         if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
-            args.noise_type = args.noise_type.get_prev();
+            args.noise.noise_type = args.noise.noise_type.get_prev();
         } else {
-            args.noise_type = args.noise_type.get_next();
+            args.noise.noise_type = args.noise.noise_type.get_next();
         }
-        info!("noise type: {:?}", args.noise_type);
+        info!("noise type: {:?}", args.noise.noise_type);
         ev_updated.send(ResourceUpdatedEvent::Args);
     }
 }
@@ -165,6 +210,7 @@ fn update_display(
     display_img: Res<DisplayImage>,
     args: Res<Args>,
     seed: Res<RandomSeed>,
+    gradient: Res<DisplayGradient>,
     window: Query<&Window>,
     mut images: ResMut<Assets<Image>>,
 ) {
@@ -188,21 +234,17 @@ fn update_display(
     let mut rng = ChaChaRng::seed_from_u64(seed.0);
     let window_w = window.single().resolution.width() as u32;
     let window_h = window.single().resolution.height() as u32;
-    let noise_fn = args.get_scaled_noise(rng.gen(), window_w, window_h);
-
-    // TODO: read from palette files
-    let colors: Vec<_> = (0..5)
-        .map(|i| {
-            let range = Uniform::new(0., 1. / 5. * (i + 1) as f64);
-            let r = rng.sample(range);
-            let g = rng.sample(range);
-            let b = rng.sample(range);
-            LinSrgb::new(r, g, b)
-        })
-        .collect();
+    let [noise_seed] = args.noise.seeds(&mut rng);
+    let noise_fn = args.get_scaled_noise(noise_seed, window_w, window_h);
 
     // let pixmap = paint_noise(window_w, window_h, &mut rng);
-    let pixmap = paint_noise(&noise_fn, &colors, window_w, window_h);
+    let pixmap = paint_noise(
+        &noise_fn,
+        &gradient.gradient,
+        args.palette.palette_lookup,
+        window_w,
+        window_h,
+    );
 
     let bvy_img = images.get_mut(&display_img.0).unwrap();
     let rgba = RgbaImage::from_raw(window_w, window_h, pixmap.data().into()).unwrap();
@@ -219,8 +261,10 @@ fn paint_circle_flag<R: Rng>(width: u32, height: u32, rng: &mut R) -> Pixmap {
         rng.sample(color_range),
         255,
     ));
-    let mut paint = Paint::default();
-    paint.anti_alias = true;
+    let mut paint = Paint {
+        anti_alias: true,
+        ..Default::default()
+    };
     paint.set_color_rgba8(
         rng.sample(color_range),
         rng.sample(color_range),
@@ -244,29 +288,15 @@ fn paint_circle_flag<R: Rng>(width: u32, height: u32, rng: &mut R) -> Pixmap {
     pixmap
 }
 
-fn paint_noise<N: NoiseFn<f64, 2>>(
+fn paint_noise<N: NoiseFn<f64, 2> + Sync>(
     noise_fn: &N,
-    colors: &Vec<Rgb<Linear<Srgb>, f64>>,
+    gradient: &PaletteGradient,
+    lookup: PaletteLookup,
     width: u32,
     height: u32,
 ) -> Pixmap {
-    let mut pixmap = Pixmap::new(width, height).unwrap();
-    let pixels = pixmap.pixels_mut();
-    let gradient = Gradient::new(colors.clone());
-
-    for i in (0..(width * height)).progress() {
-        let x = i % width;
-        let y = i / width;
-        let v = ((noise_fn.get([x as f64, y as f64]) + 1.) / 2.).clamp(0., 1.);
-        let color = gradient.get(v);
-        // TODO: convert with convenience function
-        pixels[i as usize] = PremultipliedColorU8::from_rgba(
-            (color.red * 255.) as u8,
-            (color.green * 255.) as u8,
-            (color.blue * 255.) as u8,
-            255,
-        )
-        .unwrap();
-    }
-    pixmap
+    par_paint_noise(noise_fn, width, height, |v| {
+        let v = ((v + 1.) / 2.).clamp(0., 1.);
+        color_to_pixel(&gradient.lookup(v, lookup))
+    })
 }
@@ -0,0 +1,76 @@
+//! Render palettes as labeled swatches with a gradient strip underneath, to see
+//! what a palette contains before using it in a long render.
+
+use std::process;
+
+use clap::Parser;
+use proc_art::skia_colors::{
+    render_palette_preview, Interpolation, PaletteOp, PaletteSpec, PreviewOptions,
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Render palettes as labeled swatches with a gradient strip underneath
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about=None)]
+struct Args {
+    /// bundled palette names, palette files or generators (gen:triadic), one row each
+    #[arg(required = true)]
+    palettes: Vec<PaletteSpec>,
+
+    /// palette operations applied to every palette in order, e.g. sort:lum, reverse,
+    /// darken:0.1, desaturate:0.3, every:2 or pick:0,3
+    #[arg(long)]
+    palette_op: Vec<PaletteOp>,
+
+    #[arg(short, long, default_value_t = String::from("palette_preview.png"))]
+    out: String,
+
+    /// random seed for generated palettes
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(long, default_value_t = 96)]
+    swatch_width: u32,
+
+    #[arg(long, default_value_t = 64)]
+    swatch_height: u32,
+
+    /// height of the gradient strip, 0 to leave it out
+    #[arg(long, default_value_t = 24)]
+    gradient_height: u32,
+
+    /// color space the gradient strip is blended in
+    #[arg(long, value_enum, default_value_t = Interpolation::Oklab)]
+    interpolation: Interpolation,
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut rng = match args.seed {
+        Some(n) => ChaCha8Rng::seed_from_u64(n),
+        None => ChaCha8Rng::from_entropy(),
+    };
+    let palettes: Vec<_> = args
+        .palettes
+        .iter()
+        .map(|spec| {
+            spec.load_with_rng(&mut rng)
+                .and_then(|p| Ok(p.apply_ops(&args.palette_op)?))
+                .unwrap_or_else(|e| {
+                    eprintln!("error: {}: {}", spec, e);
+                    process::exit(1);
+                })
+        })
+        .collect();
+
+    let opts = PreviewOptions {
+        swatch_width: args.swatch_width,
+        swatch_height: args.swatch_height,
+        gradient_height: args.gradient_height,
+        interpolation: args.interpolation,
+        ..Default::default()
+    };
+    let pixmap = render_palette_preview(&palettes, &opts);
+    pixmap.save_png(args.out).unwrap();
+}
@@ -0,0 +1,277 @@
+//! Flow fields for walks and streamlines to follow.
+//!
+//! A [`FlowField`] gives a direction and speed at each point of the plane.
+//! Fields are made from noise, as two channels, the curl of one channel or the
+//! angle of one channel, and combined by wrapping them in the combinators
+//! below, e.g. a normalized field with a constant bias:
+//!
+//! ```
+//! # use nalgebra::Vector2;
+//! # use noise::Perlin;
+//! # use proc_art::flow::{Angle, Constant, FlowField, Normalize, Sum};
+//! let flow = Normalize::new(Sum::new(
+//!     Angle::new(Perlin::new(1)),
+//!     Constant(Vector2::new(0.4, 0.4)),
+//! ));
+//! let direction = flow.sample(Vector2::new(0.3, 0.7));
+//! ```
+
+use std::f64::consts::TAU;
+
+use clap::ValueEnum;
+use na::{Rotation2, Vector2};
+use noise::NoiseFn;
+
+use crate::noise::{Curl, FiniteDifference, WarpField};
+
+mod integrate;
+mod spacing;
+
+pub use integrate::{IntegrationMethod, Integrator, IntegratorArgs, Streamline};
+pub use spacing::{EvenSpacingArgs, EvenlySpaced};
+
+/// A function from 2D points to 2D vectors.
+pub trait FlowField {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64>;
+}
+
+impl<F: FlowField + ?Sized> FlowField for Box<F> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        (**self).sample(pos)
+    }
+}
+
+impl<F: FlowField + ?Sized> FlowField for &F {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        (**self).sample(pos)
+    }
+}
+
+/// Unit vectors at an angle from scalar noise, `theta = noise * 2π * turns`.
+pub struct Angle<Source> {
+    pub source: Source,
+    /// Full turns across a unit change of the noise.
+    pub turns: f64,
+}
+
+impl<Source> Angle<Source> {
+    pub fn new(source: Source) -> Self {
+        Angle { source, turns: 1. }
+    }
+
+    pub fn set_turns(self, turns: f64) -> Self {
+        Angle { turns, ..self }
+    }
+}
+
+impl<Source: NoiseFn<f64, 2>> FlowField for Angle<Source> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        let theta = self.source.get(pos.into()) * TAU * self.turns;
+        Vector2::new(theta.cos(), theta.sin())
+    }
+}
+
+/// The same vector everywhere.
+pub struct Constant(pub Vector2<f64>);
+
+impl FlowField for Constant {
+    fn sample(&self, _pos: Vector2<f64>) -> Vector2<f64> {
+        self.0
+    }
+}
+
+/// The sum of two fields.
+pub struct Sum<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+impl<A, B> Sum<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Sum { a, b }
+    }
+}
+
+impl<A: FlowField, B: FlowField> FlowField for Sum<A, B> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        self.a.sample(pos) + self.b.sample(pos)
+    }
+}
+
+/// A field with its vectors multiplied by `factor`.
+pub struct Scale<Source> {
+    pub source: Source,
+    pub factor: f64,
+}
+
+impl<Source> Scale<Source> {
+    pub fn new(source: Source, factor: f64) -> Self {
+        Scale { source, factor }
+    }
+}
+
+impl<Source: FlowField> FlowField for Scale<Source> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        self.source.sample(pos) * self.factor
+    }
+}
+
+/// A field with its vectors rotated counterclockwise by `angle` radians.  The field
+/// itself stays in place.
+pub struct Rotate<Source> {
+    pub source: Source,
+    rotation: Rotation2<f64>,
+}
+
+impl<Source> Rotate<Source> {
+    pub fn new(source: Source, angle: f64) -> Self {
+        Rotate {
+            source,
+            rotation: Rotation2::new(angle),
+        }
+    }
+}
+
+impl<Source: FlowField> FlowField for Rotate<Source> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        self.rotation * self.source.sample(pos)
+    }
+}
+
+/// A field with its vectors scaled to unit length.  Zero vectors stay zero.
+pub struct Normalize<Source> {
+    pub source: Source,
+}
+
+impl<Source> Normalize<Source> {
+    pub fn new(source: Source) -> Self {
+        Normalize { source }
+    }
+}
+
+impl<Source: FlowField> FlowField for Normalize<Source> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        let v = self.source.sample(pos);
+        v.try_normalize(0.).unwrap_or(v)
+    }
+}
+
+/// A field sampled at positions multiplied by `scale`, e.g. to map pixels to noise
+/// units.
+pub struct ScalePosition<Source> {
+    pub source: Source,
+    pub scale: f64,
+}
+
+impl<Source> ScalePosition<Source> {
+    pub fn new(source: Source, scale: f64) -> Self {
+        ScalePosition { source, scale }
+    }
+}
+
+impl<Source: FlowField> FlowField for ScalePosition<Source> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        self.source.sample(pos * self.scale)
+    }
+}
+
+/// How flow is derived from noise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FieldType {
+    /// One noise function per axis.
+    #[default]
+    Channels,
+    /// The curl of one noise function, with no sinks or sources.
+    Curl,
+    /// Unit vectors at an angle given by one noise function.
+    Angle,
+}
+
+// Flow options to flatten into a binary's CLI.
+#[derive(clap::Args, Debug, Clone)]
+pub struct FlowArgs {
+    /// derive flow from two noise channels, from the curl of one, which has no sinks or
+    /// sources for walks to pile up in, or from the angle of one
+    #[arg(long, value_enum, default_value_t = FieldType::Channels)]
+    pub field: FieldType,
+
+    /// distance between samples when differentiating noise for curl flow
+    #[arg(long, default_value_t = 1e-4)]
+    pub curl_epsilon: f64,
+
+    /// full turns of angle flow across a unit change of the noise
+    #[arg(long, default_value_t = 1.)]
+    pub angle_turns: f64,
+}
+
+impl FlowArgs {
+    /// A field from noise functions `x` and `y`.  Curl and angle fields use `x` alone.
+    pub fn build(
+        &self,
+        x: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+        y: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+    ) -> Box<dyn FlowField + Send + Sync> {
+        match self.field {
+            FieldType::Channels => Box::new(WarpField { x, y }),
+            FieldType::Curl => Box::new(Curl::new(
+                FiniteDifference::new(x).set_epsilon(self.curl_epsilon),
+            )),
+            FieldType::Angle => Box::new(Angle::new(x).set_turns(self.angle_turns)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use noise::Perlin;
+
+    use super::*;
+    use crate::noise::test_util::X;
+
+    #[test]
+    fn test_combinators() {
+        let bias = Constant(Vector2::new(1., 0.));
+        let flow = Rotate::new(
+            Sum::new(bias, Scale::new(Constant(Vector2::y()), 2.)),
+            FRAC_PI_2,
+        );
+        assert!((flow.sample(Vector2::zeros()) - Vector2::new(-2., 1.)).norm() < 1e-12);
+
+        let flow = Normalize::new(flow);
+        assert!((flow.sample(Vector2::zeros()).norm() - 1.).abs() < 1e-12);
+        assert_eq!(
+            Normalize::new(Constant(Vector2::zeros())).sample(Vector2::x()),
+            Vector2::zeros()
+        );
+
+        // a quarter turn at x = 0.25, scaled from x = 2.5
+        let angle = ScalePosition::new(Angle::new(X), 0.1);
+        assert!((angle.sample(Vector2::new(2.5, 9.)) - Vector2::y()).norm() < 1e-12);
+        let angle = Angle::new(X).set_turns(0.5);
+        assert!((angle.sample(Vector2::new(0.5, 0.)) - Vector2::y()).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_flow_args_fields() {
+        let flow = |field| FlowArgs {
+            field,
+            curl_epsilon: 1e-4,
+            angle_turns: 1.,
+        };
+        let pos = Vector2::new(0.3, 0.7);
+        let channels = flow(FieldType::Channels).build(Box::new(Perlin::new(1)), Box::new(X));
+        assert_eq!(
+            channels.sample(pos),
+            Vector2::new(Perlin::new(1).get([0.3, 0.7]), 0.3)
+        );
+
+        // curl flow is perpendicular to the gradient, so follows contours of the noise
+        let curl = flow(FieldType::Curl).build(Box::new(X), Box::new(Perlin::new(1)));
+        assert!((curl.sample(pos) - Vector2::new(0., -1.)).norm() < 1e-9);
+
+        let angle = flow(FieldType::Angle).build(Box::new(Perlin::new(1)), Box::new(X));
+        assert!((angle.sample(pos).norm() - 1.).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,360 @@
+//! Evenly spaced streamlines, after Jobard and Lefer, "Creating Evenly-Spaced
+//! Streamlines of Arbitrary Density" (1997).
+//!
+//! Streamlines are traced one at a time.  Each stops when it comes within
+//! `d_test` of any earlier line, or of itself, and new lines start `d_sep` to
+//! either side of the lines already drawn, so the plane fills with lines that
+//! never cross or touch.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use na::Vector2;
+
+use super::{FlowField, Integrator, Scale};
+
+/// A sample of an accepted or growing streamline.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    pos: Vector2<f64>,
+    line: usize,
+    /// Distance along the line from its seed, negative behind it.
+    arc: f64,
+}
+
+/// Samples binned into square cells, to find the samples near a point quickly.
+struct SpatialHash {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<Sample>>,
+}
+
+impl SpatialHash {
+    fn new(cell_size: f64) -> Self {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, pos: Vector2<f64>) -> (i64, i64) {
+        (
+            (pos.x / self.cell_size).floor() as i64,
+            (pos.y / self.cell_size).floor() as i64,
+        )
+    }
+
+    fn insert(&mut self, sample: Sample) {
+        self.cells
+            .entry(self.cell(sample.pos))
+            .or_default()
+            .push(sample);
+    }
+
+    /// Remove the samples of `line`, which were inserted at `points`.
+    fn remove_line(&mut self, line: usize, points: &[Vector2<f64>]) {
+        let cells: HashSet<_> = points.iter().map(|pos| self.cell(*pos)).collect();
+        for cell in cells {
+            if let Some(samples) = self.cells.get_mut(&cell) {
+                samples.retain(|s| s.line != line);
+            }
+        }
+    }
+
+    /// Whether any sample lies within `distance` of `pos`, which must be at most the
+    /// cell size.  Samples for which `ignore` is true don't count.
+    fn any_within(
+        &self,
+        pos: Vector2<f64>,
+        distance: f64,
+        ignore: impl Fn(&Sample) -> bool,
+    ) -> bool {
+        let (cx, cy) = self.cell(pos);
+        (cx - 1..=cx + 1)
+            .flat_map(|x| (cy - 1..=cy + 1).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .any(|s| (s.pos - pos).norm() < distance && !ignore(s))
+    }
+}
+
+/// Places evenly spaced streamlines over a rectangle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvenlySpaced {
+    /// Distance between new lines and the lines they start beside.
+    pub d_sep: f64,
+    /// Distance from other lines at which a line stops, at most `d_sep`.
+    pub d_test: f64,
+    /// Longest a line may grow in each direction from its seed, in integration time.
+    pub max_length: f64,
+    /// Lines with fewer samples than this are dropped.
+    pub min_samples: usize,
+}
+
+impl EvenlySpaced {
+    pub const DEFAULT_TEST_RATIO: f64 = 0.5;
+
+    /// Panics unless `d_sep` is positive and finite.
+    pub fn new(d_sep: f64) -> Self {
+        assert!(d_sep.is_finite() && d_sep > 0., "d_sep must be positive");
+        EvenlySpaced {
+            d_sep,
+            d_test: d_sep * Self::DEFAULT_TEST_RATIO,
+            max_length: f64::INFINITY,
+            min_samples: 3,
+        }
+    }
+
+    /// Panics unless `d_test` is positive.
+    pub fn set_d_test(self, d_test: f64) -> Self {
+        assert!(d_test > 0., "d_test must be positive");
+        EvenlySpaced {
+            d_test: d_test.min(self.d_sep),
+            ..self
+        }
+    }
+
+    pub fn set_max_length(self, max_length: f64) -> Self {
+        EvenlySpaced { max_length, ..self }
+    }
+
+    pub fn set_min_samples(self, min_samples: usize) -> Self {
+        EvenlySpaced {
+            min_samples,
+            ..self
+        }
+    }
+
+    /// Streamlines of `field` inside `[0, size[0]] x [0, size[1]]`, as points in order.
+    ///
+    /// The first line starts in the middle.  Lines then start beside earlier lines
+    /// until there's no room left, and then at the first of `seeds` with room, which
+    /// fills in regions the lines never reached.
+    pub fn streamlines<F>(
+        &self,
+        field: &F,
+        integrator: &Integrator,
+        size: [f64; 2],
+        seeds: impl IntoIterator<Item = Vector2<f64>>,
+    ) -> Vec<Vec<Vector2<f64>>>
+    where
+        F: FlowField + ?Sized,
+    {
+        let mut hash = SpatialHash::new(self.d_sep);
+        let mut lines: Vec<Vec<Vector2<f64>>> = vec![];
+        let in_bounds = |pos: Vector2<f64>| {
+            (0. ..=size[0]).contains(&pos.x) && (0. ..=size[1]).contains(&pos.y)
+        };
+        let has_room = |hash: &SpatialHash, pos: Vector2<f64>| {
+            in_bounds(pos) && !hash.any_within(pos, self.d_sep, |_| false)
+        };
+
+        let mut seeds = std::iter::once(Vector2::new(size[0], size[1]) / 2.).chain(seeds);
+        // points beside accepted lines, in the order the lines were accepted
+        let mut candidates = VecDeque::new();
+        for id in 0.. {
+            let seed = loop {
+                match candidates.pop_front() {
+                    Some(pos) if has_room(&hash, pos) => break Some(pos),
+                    Some(_) => continue,
+                    None => break seeds.find(|pos| has_room(&hash, *pos)),
+                }
+            };
+            let Some(seed) = seed else {
+                break;
+            };
+            let line = self.trace(field, integrator, &mut hash, id, seed, &in_bounds);
+            if line.len() >= self.min_samples {
+                candidates.extend(self.neighbor_seeds(&line));
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Points `d_sep` to either side of `line`, at each of its samples.
+    fn neighbor_seeds(&self, line: &[Vector2<f64>]) -> Vec<Vector2<f64>> {
+        let mut seeds = vec![];
+        for i in 0..line.len() {
+            let (prev, next) = (line[i.saturating_sub(1)], line[(i + 1).min(line.len() - 1)]);
+            if let Some(tangent) = (next - prev).try_normalize(0.) {
+                let normal = Vector2::new(-tangent.y, tangent.x) * self.d_sep;
+                seeds.extend([line[i] + normal, line[i] - normal]);
+            }
+        }
+        seeds
+    }
+
+    /// Trace line `id` both ways from `seed`, adding its samples to `hash`.
+    fn trace<F>(
+        &self,
+        field: &F,
+        integrator: &Integrator,
+        hash: &mut SpatialHash,
+        id: usize,
+        seed: Vector2<f64>,
+        in_bounds: &impl Fn(Vector2<f64>) -> bool,
+    ) -> Vec<Vector2<f64>>
+    where
+        F: FlowField + ?Sized,
+    {
+        hash.insert(Sample {
+            pos: seed,
+            line: id,
+            arc: 0.,
+        });
+        let mut halves = [1., -1.].map(|direction| {
+            let field = Scale::new(field, direction);
+            let mut points = vec![];
+            let (mut prev, mut arc) = (seed, 0.);
+            for pos in integrator.streamline(&field, seed, self.max_length).skip(1) {
+                let step = (pos - prev).norm();
+                arc += step * direction;
+                // a line's own samples only count once it has curved back on itself
+                let near_self = |s: &Sample| s.line == id && (s.arc - arc).abs() < 2. * self.d_test;
+                if step < 1e-9 || !in_bounds(pos) || hash.any_within(pos, self.d_test, near_self) {
+                    break;
+                }
+                hash.insert(Sample { pos, line: id, arc });
+                points.push(pos);
+                prev = pos;
+            }
+            points
+        });
+        let [forward, backward] = &mut halves;
+        backward.reverse();
+        backward.push(seed);
+        backward.append(forward);
+        if backward.len() < self.min_samples {
+            // the seed stays, so nothing starts here again
+            hash.remove_line(id, backward);
+            hash.insert(Sample {
+                pos: seed,
+                line: id,
+                arc: 0.,
+            });
+        }
+        std::mem::take(backward)
+    }
+}
+
+// Evenly spaced streamline options to flatten into a binary's CLI.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct EvenSpacingArgs {
+    /// draw evenly spaced streamlines this far apart, in pixels, instead of random walks
+    #[arg(long, value_parser = parse_distance)]
+    pub d_sep: Option<f64>,
+
+    /// distance from other lines at which streamlines stop [default: half of --d-sep]
+    #[arg(long, requires = "d_sep", value_parser = parse_distance)]
+    pub d_test: Option<f64>,
+}
+
+fn parse_distance(s: &str) -> Result<f64, String> {
+    let distance: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if distance.is_finite() && distance > 0. {
+        Ok(distance)
+    } else {
+        Err(String::from("must be a positive number"))
+    }
+}
+
+impl EvenSpacingArgs {
+    /// The placement, if `--d-sep` was given.
+    pub fn evenly_spaced(&self) -> Option<EvenlySpaced> {
+        let placement = EvenlySpaced::new(self.d_sep?);
+        Some(match self.d_test {
+            Some(d_test) => placement.set_d_test(d_test),
+            None => placement,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{Angle, IntegrationMethod, Normalize};
+
+    use noise::Perlin;
+
+    /// The closest two points of different lines.
+    fn min_separation(lines: &[Vec<Vector2<f64>>]) -> f64 {
+        let mut min = f64::INFINITY;
+        for (i, a) in lines.iter().enumerate() {
+            for b in &lines[i + 1..] {
+                for p in a {
+                    for q in b {
+                        min = min.min((p - q).norm());
+                    }
+                }
+            }
+        }
+        min
+    }
+
+    #[test]
+    fn test_evenly_spaced_streamlines() {
+        let field = Normalize::new(Angle::new(Perlin::new(3)).set_turns(0.3));
+        let field = crate::flow::ScalePosition::new(field, 0.02);
+        let integrator = Integrator::new(IntegrationMethod::Rk4, 1.);
+        let placement = EvenlySpaced::new(8.).set_d_test(4.);
+        let size = [120., 80.];
+        let lines = placement.streamlines(&field, &integrator, size, []);
+
+        assert!(lines.len() > 5);
+        assert!(min_separation(&lines) >= 4. - 1e-9);
+        for line in &lines {
+            assert!(line.len() >= placement.min_samples);
+            for p in line {
+                assert!((0. ..=size[0]).contains(&p.x) && (0. ..=size[1]).contains(&p.y));
+            }
+        }
+        // no gap wider than a couple of separations is left bare
+        let mut hash = SpatialHash::new(placement.d_sep * 2.);
+        for (line, points) in lines.iter().enumerate() {
+            for &pos in points {
+                hash.insert(Sample { pos, line, arc: 0. });
+            }
+        }
+        for x in (4..120).step_by(8) {
+            for y in (4..80).step_by(8) {
+                let pos = Vector2::new(x as f64, y as f64);
+                assert!(
+                    hash.any_within(pos, placement.d_sep * 2., |_| false),
+                    "{}",
+                    pos
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_spatial_hash_remove_line() {
+        let mut hash = SpatialHash::new(1.);
+        let points = [Vector2::new(0.5, 0.5), Vector2::new(2.5, 0.5)];
+        for (line, pos) in [(0, points[0]), (0, points[1]), (1, Vector2::new(2.6, 0.4))] {
+            hash.insert(Sample { pos, line, arc: 0. });
+        }
+        hash.remove_line(0, &points);
+        assert!(!hash.any_within(points[0], 1., |_| false));
+        assert!(hash.any_within(points[1], 1., |_| false));
+        assert!(!hash.any_within(points[1], 1., |s| s.line == 1));
+    }
+
+    #[test]
+    fn test_even_spacing_args() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            spacing: EvenSpacingArgs,
+        }
+
+        for bad in [["--d-sep", "0"], ["--d-sep", "-2"], ["--d-sep", "inf"]] {
+            assert!(Cli::try_parse_from(["test", bad[0], bad[1]]).is_err());
+        }
+        assert!(Cli::try_parse_from(["test", "--d-sep", "4", "--d-test", "0"]).is_err());
+        let args = Cli::parse_from(["test", "--d-sep", "4", "--d-test", "1"]).spacing;
+        let placement = args.evenly_spaced().unwrap();
+        assert_eq!((placement.d_sep, placement.d_test), (4., 1.));
+    }
+}
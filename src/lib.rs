@@ -1,4 +1,9 @@
 extern crate nalgebra as na;
 
+pub mod flow;
 pub mod noise;
+pub mod palette_extract;
+pub mod points;
+pub mod raster;
+pub mod ribbon;
 pub mod skia_colors;
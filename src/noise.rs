@@ -1,61 +1,265 @@
 use clap::ValueEnum;
-use noise::{Fbm, NoiseFn, Perlin, Simplex};
+use noise::{
+    core::worley::{distance_functions, worley_2d, worley_3d, worley_4d, ReturnType},
+    permutationtable::PermutationTable,
+    NoiseFn, Seedable,
+};
+use serde::{Deserialize, Serialize};
 
-/// TODO: deprecate this in favor of configured values
+mod animate;
+mod args;
+mod config;
+mod field;
+mod graph;
+mod sampled;
+mod stats;
+mod tile;
+mod warp;
 
-/// NOTE: update this whenever number of selectors changes
-pub const NOISE_SELECTORS_LEN: isize = 3;
+pub use animate::{frame_path, Animation, AnimationArgs, Looping, TimeMode, TimeSlice};
+pub use args::NoiseArgs;
+pub use config::{BaseNoise, FractalType, LoadNoiseConfigError, NoiseConfig, NoiseConfigArgs};
+pub use field::{divergence, Curl, FiniteDifference, Gradient};
+pub use graph::{LoadNoiseGraphError, NoiseNode, WarpFieldNode};
+pub use sampled::{GridInterpolation, LoadSampledFieldError, SampledField};
+pub use stats::{NoiseStats, Normalization, NormalizeArgs, Normalized};
+pub use tile::Tileable;
+pub use warp::{DomainWarp, WarpField};
 
-/// A flat enum for selecting noise functions as a CLI option or config variable.
-#[derive(Debug, Clone, Copy, ValueEnum)]
+/// A flat enum of noise presets, for cycling through or selecting as a CLI option.
+/// See [`NoiseConfig`] for full control.
+///
+/// Fractal variants sum octaves of Perlin noise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
 pub enum NoiseSelector {
     Simplex,
+    #[default]
     Perlin,
     FbmPerlin,
+    /// Cellular noise, shaped by --worley-distance and --worley-return.
+    Worley,
+    /// Interpolated random values on a lattice.
+    Value,
+    OpenSimplex,
+    SuperSimplex,
+    /// Absolute value of each octave, giving rounded lumps.
+    Billow,
+    /// Inverted absolute value of each octave, giving sharp ridges.
+    RidgedMulti,
+    /// Octaves weighted by the previous octave, smooth valleys and rough peaks.
+    HybridMulti,
+    /// Octaves multiplied by the running total, rough peaks and smooth lowlands.
+    BasicMulti,
+}
+
+/// Distance function between a point and the Worley seed points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorleyDistance {
+    #[default]
+    Euclidean,
+    EuclideanSquared,
+    Manhattan,
+    Chebyshev,
+    Quadratic,
+}
+
+/// What Worley noise outputs for a point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorleyReturn {
+    /// A random value per cell, giving flat shards.
+    #[default]
+    Value,
+    /// Distance to the nearest seed point, giving cell outlines.
+    Distance,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorleyOptions {
+    pub distance: WorleyDistance,
+    pub return_type: WorleyReturn,
+}
+
+impl WorleyOptions {
+    pub(crate) fn build(&self, seed: u32) -> SyncWorley {
+        let return_type = match self.return_type {
+            WorleyReturn::Value => ReturnType::Value,
+            WorleyReturn::Distance => ReturnType::Distance,
+        };
+        let distance = match self.distance {
+            WorleyDistance::Euclidean => distance_functions::euclidean,
+            WorleyDistance::EuclideanSquared => distance_functions::euclidean_squared,
+            WorleyDistance::Manhattan => distance_functions::manhattan,
+            WorleyDistance::Chebyshev => distance_functions::chebyshev,
+            WorleyDistance::Quadratic => distance_functions::quadratic,
+        };
+        SyncWorley {
+            distance,
+            return_type,
+            ..SyncWorley::new(seed)
+        }
+    }
+}
+
+/// Worley noise that can be shared between threads.
+///
+/// Samples the same noise as [`noise::Worley`] at its default frequency, which
+/// keeps its distance function in an `Rc` and so is neither `Send` nor `Sync`.
+#[derive(Clone)]
+pub struct SyncWorley {
+    pub distance: fn(&[f64], &[f64]) -> f64,
+    pub return_type: ReturnType,
+    seed: u32,
+    hasher: PermutationTable,
+}
+
+impl SyncWorley {
+    pub fn new(seed: u32) -> Self {
+        SyncWorley {
+            distance: distance_functions::euclidean,
+            return_type: ReturnType::Value,
+            seed,
+            hasher: PermutationTable::new(seed),
+        }
+    }
+}
+
+impl Default for SyncWorley {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Seedable for SyncWorley {
+    fn set_seed(self, seed: u32) -> Self {
+        SyncWorley {
+            seed,
+            hasher: PermutationTable::new(seed),
+            ..self
+        }
+    }
+
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+}
+
+impl NoiseFn<f64, 2> for SyncWorley {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        worley_2d(&self.hasher, self.distance, self.return_type, point)
+    }
+}
+
+impl NoiseFn<f64, 3> for SyncWorley {
+    fn get(&self, point: [f64; 3]) -> f64 {
+        worley_3d(&self.hasher, self.distance, self.return_type, point)
+    }
+}
+
+impl NoiseFn<f64, 4> for SyncWorley {
+    fn get(&self, point: [f64; 4]) -> f64 {
+        worley_4d(&self.hasher, self.distance, self.return_type, point)
+    }
 }
 
 impl NoiseSelector {
-    pub fn get_noise_2d(&self, seed: u32) -> Box<dyn NoiseFn<f64, 2>> {
-        match self {
-            Self::Simplex => Box::new(Simplex::new(seed)),
-            Self::Perlin => Box::new(Perlin::new(seed)),
-            Self::FbmPerlin => Box::new(Fbm::<Perlin>::new(seed)),
+    /// The config this preset stands for.
+    pub fn config(&self) -> NoiseConfig {
+        let (base, fractal) = match self {
+            Self::Simplex => (BaseNoise::Simplex, FractalType::None),
+            Self::Perlin => (BaseNoise::Perlin, FractalType::None),
+            Self::FbmPerlin => (BaseNoise::Perlin, FractalType::Fbm),
+            Self::Worley => (BaseNoise::Worley, FractalType::None),
+            Self::Value => (BaseNoise::Value, FractalType::None),
+            Self::OpenSimplex => (BaseNoise::OpenSimplex, FractalType::None),
+            Self::SuperSimplex => (BaseNoise::SuperSimplex, FractalType::None),
+            Self::Billow => (BaseNoise::Perlin, FractalType::Billow),
+            Self::RidgedMulti => (BaseNoise::Perlin, FractalType::RidgedMulti),
+            Self::HybridMulti => (BaseNoise::Perlin, FractalType::HybridMulti),
+            Self::BasicMulti => (BaseNoise::Perlin, FractalType::BasicMulti),
+        };
+        NoiseConfig {
+            base,
+            fractal,
+            ..Default::default()
         }
     }
 
+    pub fn get_noise_2d(&self, seed: u32) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        self.config().build(seed)
+    }
+
+    /// Like [`NoiseSelector::get_noise_2d`], with options for the Worley variant.
+    pub fn get_noise_2d_with(
+        &self,
+        seed: u32,
+        worley: &WorleyOptions,
+    ) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        NoiseConfig {
+            worley: *worley,
+            ..self.config()
+        }
+        .build(seed)
+    }
+
+    fn index(&self) -> usize {
+        Self::value_variants()
+            .iter()
+            .position(|v| v == self)
+            .unwrap()
+    }
+
     pub fn get_next(&self) -> Self {
-        Self::from((*self as isize + 1) % NOISE_SELECTORS_LEN)
+        let variants = Self::value_variants();
+        variants[(self.index() + 1) % variants.len()]
     }
 
     pub fn get_prev(&self) -> Self {
-        Self::from((*self as isize + NOISE_SELECTORS_LEN - 1) % NOISE_SELECTORS_LEN)
+        let variants = Self::value_variants();
+        variants[(self.index() + variants.len() - 1) % variants.len()]
     }
 }
 
 impl From<isize> for NoiseSelector {
     fn from(idx: isize) -> Self {
-        match idx {
-            0 => Self::Simplex,
-            1 => Self::Perlin,
-            2 => Self::FbmPerlin,
-            _ => Self::default(),
-        }
+        usize::try_from(idx)
+            .ok()
+            .and_then(|idx| Self::value_variants().get(idx))
+            .copied()
+            .unwrap_or_default()
     }
 }
 
-impl Default for NoiseSelector {
-    fn default() -> Self {
-        Self::Perlin
+/// Fixtures shared by the noise and flow tests.
+#[cfg(test)]
+pub(crate) mod test_util {
+    use noise::NoiseFn;
+
+    /// Returns the x coordinate of the point.
+    pub struct X;
+
+    impl NoiseFn<f64, 2> for X {
+        fn get(&self, [x, _]: [f64; 2]) -> f64 {
+            x
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::noise::{NoiseSelector, NOISE_SELECTORS_LEN};
+    use clap::ValueEnum;
+    use noise::{
+        core::worley::{distance_functions, ReturnType},
+        NoiseFn, Worley,
+    };
+
+    use crate::noise::{NoiseSelector, WorleyDistance, WorleyOptions, WorleyReturn};
 
     #[test]
     fn test_noise_selector_from_idx() {
-        for i in 0..NOISE_SELECTORS_LEN {
+        for i in 0..NoiseSelector::value_variants().len() as isize {
             assert_eq!(i, NoiseSelector::from(i) as isize)
         }
         // for larger numbers, should be equal to default
@@ -63,5 +267,50 @@ mod tests {
             NoiseSelector::default() as isize,
             NoiseSelector::from(255) as isize
         );
+        assert_eq!(NoiseSelector::default(), NoiseSelector::from(-1));
+    }
+
+    #[test]
+    fn test_noise_selector_cycles_through_all() {
+        let variants = NoiseSelector::value_variants();
+        let mut selector = NoiseSelector::Simplex;
+        for expected in variants.iter().cycle().skip(1).take(variants.len()) {
+            selector = selector.get_next();
+            assert_eq!(selector, *expected);
+        }
+        assert_eq!(selector, NoiseSelector::Simplex);
+        assert_eq!(selector.get_prev(), *variants.last().unwrap());
+        assert_eq!(selector.get_prev().get_next(), selector);
+    }
+
+    #[test]
+    fn test_sync_worley_matches_worley() {
+        let options = WorleyOptions {
+            distance: WorleyDistance::Chebyshev,
+            return_type: WorleyReturn::Distance,
+        };
+        let worley = Worley::new(5)
+            .set_distance_function(distance_functions::chebyshev)
+            .set_return_type(ReturnType::Distance);
+        for point in [[0.3, 0.7], [1.9, -2.4], [-4.5, 0.1]] {
+            assert_eq!(options.build(5).get(point), worley.get(point));
+        }
+    }
+
+    #[test]
+    fn test_every_noise_is_seeded() {
+        let worley = WorleyOptions {
+            distance: WorleyDistance::Manhattan,
+            return_type: WorleyReturn::Distance,
+        };
+        let point = [0.37, 1.61];
+        for selector in NoiseSelector::value_variants() {
+            let a = selector.get_noise_2d_with(1, &worley).get(point);
+            let b = selector.get_noise_2d_with(1, &worley).get(point);
+            let c = selector.get_noise_2d_with(2, &worley).get(point);
+            assert!(a.is_finite(), "{:?}", selector);
+            assert_eq!(a, b, "{:?}", selector);
+            assert_ne!(a, c, "{:?}", selector);
+        }
     }
 }
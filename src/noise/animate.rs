@@ -0,0 +1,195 @@
+//! Animating noise by sampling it along a time dimension.
+//!
+//! Linear animations sample 3D noise at `[x, y, t]`.  Looping animations wrap
+//! time onto a circle in 4D noise, so the last frame leads back into the first.
+
+use std::{
+    f64::consts::TAU,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use noise::NoiseFn;
+
+/// How time moves through noise space.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimeMode {
+    /// Around a circle in 4D noise, returning to the start after one period.
+    #[default]
+    Loop,
+    /// Along a straight line in 3D noise.
+    Linear,
+}
+
+/// A sequence of frames spread evenly over `period` units of time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animation {
+    pub frames: usize,
+    pub period: f64,
+    pub mode: TimeMode,
+}
+
+impl Animation {
+    /// Time of a frame.  Frame `frames` would be at `period`, which for a loop is
+    /// the first frame again.
+    pub fn time(&self, frame: usize) -> f64 {
+        frame as f64 * self.period / self.frames as f64
+    }
+
+    pub fn times(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.frames).map(|frame| self.time(frame))
+    }
+}
+
+/// 3D noise that repeats every `period` units along its last axis, the time axis.
+///
+/// Time is wrapped onto a circle whose circumference is the period, so noise
+/// changes at about the same rate as it would along a line.
+pub struct Looping<Source> {
+    pub source: Source,
+    pub period: f64,
+}
+
+impl<Source> Looping<Source> {
+    /// Panics unless `period` is positive and finite.
+    pub fn new(source: Source, period: f64) -> Self {
+        assert!(period.is_finite() && period > 0., "period must be positive");
+        Looping { source, period }
+    }
+}
+
+impl<Source: NoiseFn<f64, 4>> NoiseFn<f64, 3> for Looping<Source> {
+    fn get(&self, [x, y, t]: [f64; 3]) -> f64 {
+        let (sin, cos) = (t / self.period * TAU).sin_cos();
+        let r = self.period / TAU;
+        self.source.get([x, y, r * cos, r * sin])
+    }
+}
+
+/// 2D noise sampled from 3D noise at a fixed time.
+pub struct TimeSlice<Source> {
+    pub source: Source,
+    pub time: f64,
+}
+
+impl<Source> TimeSlice<Source> {
+    pub fn new(source: Source, time: f64) -> Self {
+        TimeSlice { source, time }
+    }
+}
+
+impl<Source: NoiseFn<f64, 3>> NoiseFn<f64, 2> for TimeSlice<Source> {
+    fn get(&self, [x, y]: [f64; 2]) -> f64 {
+        self.source.get([x, y, self.time])
+    }
+}
+
+// Animation options to flatten into a binary's CLI.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct AnimationArgs {
+    /// render this many frames of an animation instead of a single image, numbered before
+    /// the output's extension
+    #[arg(long, value_parser = parse_frames)]
+    pub frames: Option<usize>,
+
+    /// distance travelled through noise space over all the frames
+    #[arg(long, default_value_t = 1., value_parser = parse_period)]
+    pub time_period: f64,
+
+    /// loop the animation seamlessly, or move in a straight line through the noise
+    #[arg(long, value_enum, default_value_t = TimeMode::Loop)]
+    pub time_mode: TimeMode,
+}
+
+fn parse_frames(s: &str) -> Result<usize, String> {
+    let frames: usize = s.parse().map_err(|e| format!("{}", e))?;
+    if frames > 0 {
+        Ok(frames)
+    } else {
+        Err(String::from("must be at least 1"))
+    }
+}
+
+fn parse_period(s: &str) -> Result<f64, String> {
+    let period: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if period.is_finite() && period > 0. {
+        Ok(period)
+    } else {
+        Err(String::from("must be a positive number"))
+    }
+}
+
+impl AnimationArgs {
+    /// The animation, if `--frames` was given.
+    pub fn animation(&self) -> Option<Animation> {
+        self.frames.map(|frames| Animation {
+            frames,
+            period: self.time_period,
+            mode: self.time_mode,
+        })
+    }
+}
+
+/// The path to save a frame of an animation to: `path` with the zero-padded frame
+/// number appended to its file stem, e.g. `out.png` becomes `out_0007.png`.
+pub fn frame_path(path: impl AsRef<Path>, frame: usize) -> PathBuf {
+    let path = path.as_ref();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}_{:04}", stem, frame);
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use noise::{OpenSimplex, Perlin};
+
+    use super::*;
+
+    #[test]
+    fn test_looping_repeats_in_time() {
+        let noise = Looping::new(OpenSimplex::new(6), 2.5);
+        for [x, y, t] in [[0.3, 0.7, 0.], [1.9, -2.4, 1.1], [4.5, 0., 2.]] {
+            let v = noise.get([x, y, t]);
+            assert!((noise.get([x, y, t + 2.5]) - v).abs() < 1e-9);
+            assert!((noise.get([x, y, t - 5.]) - v).abs() < 1e-9);
+        }
+        assert_ne!(noise.get([0.3, 0.7, 0.]), noise.get([0.3, 0.7, 1.2]));
+
+        let slice = TimeSlice::new(Perlin::new(2), 0.4);
+        assert_eq!(slice.get([1.3, 0.2]), Perlin::new(2).get([1.3, 0.2, 0.4]));
+    }
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        animation: AnimationArgs,
+    }
+
+    #[test]
+    fn test_animation_args_reject_bad_values() {
+        for args in [
+            ["test", "--frames", "0"],
+            ["test", "--time-period", "0"],
+            ["test", "--time-period", "NaN"],
+            ["test", "--time-period", "inf"],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err());
+        }
+        let cli = Cli::parse_from(["test", "--frames", "4", "--time-period", "2"]);
+        assert_eq!(cli.animation.animation().unwrap().time(1), 0.5);
+    }
+
+    #[test]
+    fn test_frame_path() {
+        assert_eq!(frame_path("out.png", 7), PathBuf::from("out_0007.png"));
+        assert_eq!(
+            frame_path("renders/noise.v2.png", 12),
+            PathBuf::from("renders/noise.v2_0012.png")
+        );
+        assert_eq!(frame_path("frames", 0), PathBuf::from("frames_0000"));
+    }
+}
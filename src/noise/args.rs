@@ -0,0 +1,158 @@
+//! Noise options shared by every binary.
+//!
+//! Binaries flatten [`NoiseArgs`] into their CLI, so each tool takes the same
+//! flags and turns them into noise the same way.  Tools that want a different
+//! default preset or scale change it with clap's `mut_arg`, e.g.
+//!
+//! ```ignore
+//! #[command(mut_arg("noise_scale", |arg| arg.default_value("4")))]
+//! ```
+
+use clap::ArgAction;
+use noise::{NoiseFn, ScalePoint};
+use rand::Rng;
+
+use super::{graph::child_seed, Animation, LoadNoiseConfigError, NoiseConfigArgs, NoiseSelector};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct NoiseArgs {
+    /// noise preset, which the other noise options adjust
+    #[arg(long, value_enum, default_value_t = NoiseSelector::Simplex)]
+    pub noise_type: NoiseSelector,
+
+    /// noise units per pixel, or across the image with --noise-norm
+    #[arg(long, default_value_t = 1.)]
+    pub noise_scale: f64,
+
+    /// normalize noise scale to size of image
+    #[arg(long, action = ArgAction::Set, num_args = 0..=1, default_value_t = false,
+          default_missing_value = "true")]
+    pub noise_norm: bool,
+
+    /// noise seed, random if omitted
+    #[arg(long)]
+    pub noise_seed: Option<u32>,
+
+    #[command(flatten)]
+    pub config: NoiseConfigArgs,
+}
+
+impl NoiseArgs {
+    /// Seeds for `N` independent noise functions.  They're derived from `--noise-seed`
+    /// if given, otherwise drawn from `rng`, which is advanced either way.
+    pub fn seeds<const N: usize>(&self, rng: &mut impl Rng) -> [u32; N] {
+        let drawn: [u32; N] = std::array::from_fn(|_| rng.gen());
+        match self.noise_seed {
+            Some(seed) => std::array::from_fn(|i| match i {
+                0 => seed,
+                i => child_seed(seed, i as u32),
+            }),
+            None => drawn,
+        }
+    }
+
+    /// Noise units per pixel of an image of `size` pixels.
+    pub fn scale(&self, size: [f64; 2]) -> f64 {
+        if self.noise_norm {
+            self.noise_scale / size[0].max(size[1])
+        } else {
+            self.noise_scale
+        }
+    }
+
+    /// The noise in noise units, before scaling to the image, or the noise for one
+    /// frame of an animation if `frame` is given.
+    pub fn build_unscaled(
+        &self,
+        seed: u32,
+        frame: Option<(&Animation, f64)>,
+    ) -> Result<Box<dyn NoiseFn<f64, 2> + Send + Sync>, LoadNoiseConfigError> {
+        let preset = self.noise_type.config();
+        match frame {
+            Some((animation, time)) => self.config.build_frame(preset, seed, animation, time),
+            None => self.config.build(preset, seed),
+        }
+    }
+
+    /// The noise over the pixels of an image of `size` pixels, or the noise for one
+    /// frame of an animation if `frame` is given.
+    pub fn build(
+        &self,
+        seed: u32,
+        size: [f64; 2],
+        frame: Option<(&Animation, f64)>,
+    ) -> Result<Box<dyn NoiseFn<f64, 2> + Send + Sync>, LoadNoiseConfigError> {
+        Ok(self.scaled(self.build_unscaled(seed, frame)?, size))
+    }
+
+    /// Like [`NoiseArgs::build`], but repeating every `size` pixels so the image tiles.
+    pub fn build_tileable(
+        &self,
+        seed: u32,
+        size: [f64; 2],
+    ) -> Result<Box<dyn NoiseFn<f64, 2> + Send + Sync>, LoadNoiseConfigError> {
+        let scale = self.scale(size);
+        let period = size.map(|n| n * scale);
+        if !period.iter().all(|p| p.is_finite() && *p > 0.) {
+            return Err(LoadNoiseConfigError::TilePeriod(period));
+        }
+        let noise = self
+            .config
+            .build_tileable(self.noise_type.config(), seed, period)?;
+        Ok(self.scaled(noise, size))
+    }
+
+    fn scaled(
+        &self,
+        noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+        size: [f64; 2],
+    ) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        Box::new(ScalePoint::new(noise).set_scale(self.scale(size)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        noise: NoiseArgs,
+    }
+
+    #[test]
+    fn test_noise_args() {
+        let cli = Cli::parse_from(["test", "--noise-scale", "8", "--noise-norm"]);
+        assert_eq!(cli.noise.noise_type, NoiseSelector::Simplex);
+        assert_eq!(cli.noise.scale([400., 200.]), 0.02);
+
+        // the same flags give the same noise, in pixels
+        let cli = Cli::parse_from(["test", "--noise-seed", "7", "--noise-type", "fbm-perlin"]);
+        let rng = &mut ChaCha8Rng::seed_from_u64(0);
+        let [seed, other] = cli.noise.seeds(rng);
+        assert_eq!(seed, 7);
+        assert_ne!(seed, other);
+        let noise = cli.noise.build(seed, [64., 64.], None).unwrap();
+        let expected = NoiseSelector::FbmPerlin.get_noise_2d(7);
+        assert_eq!(noise.get([0.3, 12.5]), expected.get([0.3, 12.5]));
+
+        let cli = Cli::parse_from(["test", "--noise-norm", "false"]);
+        assert!(!cli.noise.noise_norm);
+    }
+
+    #[test]
+    fn test_build_tileable_rejects_bad_scale() {
+        let cli = Cli::parse_from(["test", "--noise-scale", "0"]);
+        assert!(matches!(
+            cli.noise.build_tileable(1, [64., 64.]),
+            Err(LoadNoiseConfigError::TilePeriod(_))
+        ));
+        let cli = Cli::parse_from(["test", "--noise-scale", "0.1"]);
+        assert!(cli.noise.build_tileable(1, [64., 64.]).is_ok());
+    }
+}
@@ -0,0 +1,810 @@
+//! Noise functions described by configuration instead of code.
+//!
+//! A [`NoiseConfig`] can be read from a TOML file, e.g.
+//!
+//! ```toml
+//! base = "simplex"
+//! fractal = "ridged-multi"
+//! octaves = 6
+//! frequency = 3.0
+//! rotation = 30.0
+//! ```
+//!
+//! and binaries flatten [`NoiseConfigArgs`] into their CLI, so every field can
+//! also be set or overridden with flags.
+
+use std::{error::Error, fmt, fs, io, path::Path, path::PathBuf};
+
+use clap::ValueEnum;
+use noise::{
+    BasicMulti, Billow, Fbm, HybridMulti, MultiFractal, NoiseFn, OpenSimplex, Perlin,
+    PerlinSurflet, RidgedMulti, ScalePoint, Seedable, Simplex, SuperSimplex, TranslatePoint, Value,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    graph::child_seed, Animation, DomainWarp, LoadNoiseGraphError, Looping, NoiseNode,
+    NoiseSelector, Tileable, TimeMode, TimeSlice, WorleyDistance, WorleyOptions, WorleyReturn,
+};
+
+/// The noise function that fractals are built from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BaseNoise {
+    #[default]
+    Perlin,
+    Simplex,
+    OpenSimplex,
+    SuperSimplex,
+    Value,
+    Worley,
+}
+
+/// How octaves of the base noise are combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FractalType {
+    /// A single octave of the base noise.
+    #[default]
+    None,
+    Fbm,
+    Billow,
+    RidgedMulti,
+    HybridMulti,
+    BasicMulti,
+}
+
+/// A noise function: base noise, fractal layering and a transform of the input coordinates.
+///
+/// Points are rotated, then scaled by `frequency`, then translated by `offset`
+/// before being passed to the noise.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseConfig {
+    pub base: BaseNoise,
+    pub fractal: FractalType,
+    /// Number of octaves, from 1 to 32.  Defaults to the fractal type's own default.
+    pub octaves: Option<usize>,
+    /// Frequency multiplier between octaves.  Defaults to the fractal type's own default.
+    pub lacunarity: Option<f64>,
+    /// Amplitude multiplier between octaves.  Defaults to the fractal type's own default.
+    pub persistence: Option<f64>,
+    pub frequency: f64,
+    pub offset: [f64; 2],
+    /// Rotation of the input coordinates in degrees.
+    pub rotation: f64,
+    pub worley: WorleyOptions,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        NoiseConfig {
+            base: BaseNoise::default(),
+            fractal: FractalType::default(),
+            octaves: None,
+            lacunarity: None,
+            persistence: None,
+            frequency: 1.,
+            offset: [0., 0.],
+            rotation: 0.,
+            worley: WorleyOptions::default(),
+        }
+    }
+}
+
+/// Build a fractal whose octaves come from `source`, seeded `seed`, `seed + 1`, ...
+macro_rules! fractal {
+    ($fractal:ident, $config:expr, $seed:expr, $source:expr) => {{
+        let fractal = $config.configure($fractal::<T>::default());
+        let sources = (0..fractal.octaves as u32)
+            .map(|i| $source($seed.wrapping_add(i)))
+            .collect();
+        Box::new(fractal.set_sources(sources))
+    }};
+}
+
+impl NoiseConfig {
+    /// Parse a config from TOML.  Missing fields take their default values.
+    pub fn from_toml(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadNoiseConfigError> {
+        Ok(Self::from_toml(&fs::read_to_string(path)?)?)
+    }
+
+    fn configure<F: MultiFractal>(&self, mut fractal: F) -> F {
+        if let Some(octaves) = self.octaves {
+            fractal = fractal.set_octaves(octaves);
+        }
+        if let Some(lacunarity) = self.lacunarity {
+            fractal = fractal.set_lacunarity(lacunarity);
+        }
+        if let Some(persistence) = self.persistence {
+            fractal = fractal.set_persistence(persistence);
+        }
+        fractal
+    }
+
+    pub fn build(&self, seed: u32) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        let noise = match self.base {
+            BaseNoise::Perlin => self.build_fractal(seed, Perlin::new),
+            BaseNoise::Simplex => self.build_fractal(seed, Simplex::new),
+            BaseNoise::OpenSimplex => self.build_fractal(seed, OpenSimplex::new),
+            BaseNoise::SuperSimplex => self.build_fractal(seed, SuperSimplex::new),
+            BaseNoise::Value => self.build_fractal(seed, Value::new),
+            BaseNoise::Worley => self.build_fractal(seed, |seed| self.worley.build(seed)),
+        };
+        self.transform(noise)
+    }
+
+    /// Noise that changes over time, sampled at `[x, y, t]`.  See [`Animation`].
+    ///
+    /// The transform applies to x and y as in [`NoiseConfig::build`], and
+    /// `frequency` scales time as well.  Looping animations are built from 4D
+    /// noise, with the same substitutions as [`NoiseConfig::build_tileable`].
+    pub fn build_animated(
+        &self,
+        seed: u32,
+        animation: &Animation,
+    ) -> Box<dyn NoiseFn<f64, 3> + Send + Sync> {
+        match animation.mode {
+            TimeMode::Linear => {
+                let noise = match self.base {
+                    BaseNoise::Perlin => self.build_fractal(seed, Perlin::new),
+                    BaseNoise::Simplex => self.build_fractal(seed, Simplex::new),
+                    BaseNoise::OpenSimplex => self.build_fractal(seed, OpenSimplex::new),
+                    BaseNoise::SuperSimplex => self.build_fractal(seed, SuperSimplex::new),
+                    BaseNoise::Value => self.build_fractal(seed, Value::new),
+                    BaseNoise::Worley => self.build_fractal(seed, |seed| self.worley.build(seed)),
+                };
+                self.transform(noise)
+            }
+            TimeMode::Loop => Box::new(Looping::new(
+                self.transform(self.build_4d(seed)),
+                animation.period,
+            )),
+        }
+    }
+
+    /// A single frame of [`NoiseConfig::build_animated`], at `time`.
+    pub fn build_frame(
+        &self,
+        seed: u32,
+        animation: &Animation,
+        time: f64,
+    ) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        Box::new(TimeSlice::new(self.build_animated(seed, animation), time))
+    }
+
+    /// Like [`NoiseConfig::build`], but repeating every `period` units along x and y.
+    ///
+    /// Built from 4D noise on a torus, see [`Tileable`].  Rotation is ignored, and
+    /// the offset moves around the torus rather than across the plane.
+    ///
+    /// 4D Perlin noise has seams at lattice boundaries in the noise crate, so
+    /// surflet Perlin noise is used in its place.  4D simplex noise has seams
+    /// too, and super simplex noise has no 4D variant, so open simplex is used
+    /// for both.
+    pub fn build_tileable(
+        &self,
+        seed: u32,
+        period: [f64; 2],
+    ) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        let [x, y] = self.offset;
+        let noise =
+            TranslatePoint::new(ScalePoint::new(self.build_4d(seed)).set_scale(self.frequency))
+                .set_all_translations(x, 0., y, 0.);
+        Box::new(Tileable::new(noise, period))
+    }
+
+    fn build_4d(&self, seed: u32) -> Box<dyn NoiseFn<f64, 4> + Send + Sync> {
+        match self.base {
+            BaseNoise::Perlin => self.build_fractal(seed, PerlinSurflet::new),
+            BaseNoise::Simplex | BaseNoise::OpenSimplex | BaseNoise::SuperSimplex => {
+                self.build_fractal(seed, OpenSimplex::new)
+            }
+            BaseNoise::Value => self.build_fractal(seed, Value::new),
+            BaseNoise::Worley => self.build_fractal(seed, |seed| self.worley.build(seed)),
+        }
+    }
+
+    /// Apply rotation, frequency and offset to the first two axes of `noise`.
+    fn transform<const DIM: usize>(
+        &self,
+        noise: Box<dyn NoiseFn<f64, DIM> + Send + Sync>,
+    ) -> Box<dyn NoiseFn<f64, DIM> + Send + Sync> {
+        if self.frequency == 1. && self.offset == [0., 0.] && self.rotation == 0. {
+            return noise;
+        }
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        Box::new(TransformPoint {
+            source: noise,
+            rotation: [cos, sin],
+            frequency: self.frequency,
+            offset: self.offset,
+        })
+    }
+
+    fn build_fractal<T, const DIM: usize>(
+        &self,
+        seed: u32,
+        source: impl Fn(u32) -> T,
+    ) -> Box<dyn NoiseFn<f64, DIM> + Send + Sync>
+    where
+        T: Default + Seedable + NoiseFn<f64, DIM> + Send + Sync + 'static,
+        Fbm<T>: NoiseFn<f64, DIM>,
+        Billow<T>: NoiseFn<f64, DIM>,
+        RidgedMulti<T>: NoiseFn<f64, DIM>,
+        HybridMulti<T>: NoiseFn<f64, DIM>,
+        BasicMulti<T>: NoiseFn<f64, DIM>,
+    {
+        match self.fractal {
+            FractalType::None => Box::new(source(seed)),
+            FractalType::Fbm => fractal!(Fbm, self, seed, source),
+            FractalType::Billow => fractal!(Billow, self, seed, source),
+            FractalType::RidgedMulti => fractal!(RidgedMulti, self, seed, source),
+            FractalType::HybridMulti => fractal!(HybridMulti, self, seed, source),
+            FractalType::BasicMulti => fractal!(BasicMulti, self, seed, source),
+        }
+    }
+}
+
+/// Rotate and translate the first two axes of input points, and scale all of them.
+struct TransformPoint<const DIM: usize> {
+    source: Box<dyn NoiseFn<f64, DIM> + Send + Sync>,
+    /// cosine and sine of the rotation angle
+    rotation: [f64; 2],
+    frequency: f64,
+    offset: [f64; 2],
+}
+
+impl<const DIM: usize> NoiseFn<f64, DIM> for TransformPoint<DIM> {
+    fn get(&self, point: [f64; DIM]) -> f64 {
+        let [cos, sin] = self.rotation;
+        let (x, y) = (point[0], point[1]);
+        let (x, y) = (x * cos - y * sin, x * sin + y * cos);
+        let mut point = point.map(|v| v * self.frequency);
+        point[0] = x * self.frequency + self.offset[0];
+        point[1] = y * self.frequency + self.offset[1];
+        self.source.get(point)
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadNoiseConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Graph(LoadNoiseGraphError),
+    /// Tileable noise was asked for from a noise graph.
+    TileableGraph,
+    /// Tileable noise was asked for with a period that isn't positive and finite.
+    TilePeriod([f64; 2]),
+    /// Animated noise was asked for from a noise graph.
+    AnimatedGraph,
+}
+
+impl fmt::Display for LoadNoiseConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read noise config: {}", e),
+            Self::Parse(e) => write!(f, "could not parse noise config: {}", e),
+            Self::Graph(e) => write!(f, "{}", e),
+            Self::TileableGraph => write!(f, "noise graphs can't be made tileable"),
+            Self::TilePeriod([x, y]) => write!(
+                f,
+                "tileable noise needs a positive --noise-scale, got a period of {} x {}",
+                x, y
+            ),
+            Self::AnimatedGraph => write!(f, "noise graphs can't be animated"),
+        }
+    }
+}
+
+impl Error for LoadNoiseConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::Graph(e) => Some(e),
+            Self::TileableGraph | Self::TilePeriod(_) | Self::AnimatedGraph => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadNoiseConfigError {
+    fn from(value: io::Error) -> Self {
+        LoadNoiseConfigError::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for LoadNoiseConfigError {
+    fn from(value: toml::de::Error) -> Self {
+        LoadNoiseConfigError::Parse(value)
+    }
+}
+
+impl From<LoadNoiseGraphError> for LoadNoiseConfigError {
+    fn from(value: LoadNoiseGraphError) -> Self {
+        LoadNoiseConfigError::Graph(value)
+    }
+}
+
+// Noise options to flatten into a binary's CLI.
+//
+// Each binary supplies a starting config, which is replaced by `--noise-config`
+// if given.  Any other flags then override individual fields.  `--noise-graph`
+// replaces the config and its flags.  `--warp` applies to either.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct NoiseConfigArgs {
+    /// TOML file describing the noise function
+    #[arg(long)]
+    pub noise_config: Option<PathBuf>,
+
+    /// TOML or RON file describing a graph of noise functions and combinators.  Other noise
+    /// options except warping are ignored.
+    #[arg(long, conflicts_with = "noise_config")]
+    pub noise_graph: Option<PathBuf>,
+
+    /// base noise function
+    #[arg(long, value_enum)]
+    pub noise_base: Option<BaseNoise>,
+
+    /// how octaves of the base noise are layered
+    #[arg(long, value_enum)]
+    pub fractal: Option<FractalType>,
+
+    /// number of fractal octaves
+    #[arg(long)]
+    pub octaves: Option<usize>,
+
+    /// frequency multiplier between fractal octaves
+    #[arg(long, value_parser = parse_finite)]
+    pub lacunarity: Option<f64>,
+
+    /// amplitude multiplier between fractal octaves
+    #[arg(long, value_parser = parse_finite)]
+    pub persistence: Option<f64>,
+
+    /// noise frequency, multiplying any other noise scale
+    #[arg(long, value_parser = parse_frequency)]
+    pub frequency: Option<f64>,
+
+    /// translation in noise space
+    #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+    pub noise_offset: Option<Vec<f64>>,
+
+    /// rotation of the noise in degrees
+    #[arg(long, allow_negative_numbers = true)]
+    pub noise_rotation: Option<f64>,
+
+    /// distance function for worley noise
+    #[arg(long, value_enum)]
+    pub worley_distance: Option<WorleyDistance>,
+
+    /// whether worley noise outputs a value per cell or the distance to the cell's seed point
+    #[arg(long, value_enum)]
+    pub worley_return: Option<WorleyReturn>,
+
+    /// domain warp the noise, offsetting points by fields of this noise type
+    #[arg(long, value_enum)]
+    pub warp: Option<NoiseSelector>,
+
+    /// distance the warp moves points, in noise units [default: 1]
+    #[arg(long, allow_negative_numbers = true)]
+    pub warp_strength: Option<f64>,
+
+    /// number of nested warps [default: 1]
+    #[arg(long)]
+    pub warp_iterations: Option<usize>,
+
+    /// number of independent warp fields, cycled through by the iterations [default: 1]
+    #[arg(long)]
+    pub warp_fields: Option<usize>,
+
+    /// frequency of the warp fields [default: 1]
+    #[arg(long)]
+    pub warp_frequency: Option<f64>,
+
+    /// `--noise-config` as read by [`NoiseConfigArgs::load_files`]
+    #[arg(skip)]
+    config_file: Option<NoiseConfig>,
+
+    /// `--noise-graph` as read by [`NoiseConfigArgs::load_files`]
+    #[arg(skip)]
+    graph_file: Option<NoiseNode>,
+}
+
+fn parse_finite(s: &str) -> Result<f64, String> {
+    let value: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(String::from("must be a finite number"))
+    }
+}
+
+fn parse_frequency(s: &str) -> Result<f64, String> {
+    let frequency = parse_finite(s)?;
+    if frequency != 0. {
+        Ok(frequency)
+    } else {
+        Err(String::from("must be non-zero"))
+    }
+}
+
+impl NoiseConfigArgs {
+    /// Read `--noise-config` and `--noise-graph` once, for tools that build noise over
+    /// and over.  Otherwise the files are read every time noise is built.
+    pub fn load_files(&mut self) -> Result<(), LoadNoiseConfigError> {
+        if let Some(path) = &self.noise_config {
+            self.config_file = Some(NoiseConfig::load(path)?);
+        }
+        if let Some(path) = &self.noise_graph {
+            self.graph_file = Some(NoiseNode::load(path)?);
+        }
+        Ok(())
+    }
+
+    /// The config file if one was given, otherwise `preset`, with flags applied on top.
+    pub fn resolve(&self, preset: NoiseConfig) -> Result<NoiseConfig, LoadNoiseConfigError> {
+        let mut config = match (self.config_file, &self.noise_config) {
+            (Some(config), _) => config,
+            (None, Some(path)) => NoiseConfig::load(path)?,
+            (None, None) => preset,
+        };
+        if let Some(base) = self.noise_base {
+            config.base = base;
+        }
+        if let Some(fractal) = self.fractal {
+            config.fractal = fractal;
+        }
+        if let Some(octaves) = self.octaves {
+            config.octaves = Some(octaves);
+        }
+        if let Some(lacunarity) = self.lacunarity {
+            config.lacunarity = Some(lacunarity);
+        }
+        if let Some(persistence) = self.persistence {
+            config.persistence = Some(persistence);
+        }
+        if let Some(frequency) = self.frequency {
+            config.frequency = frequency;
+        }
+        if let Some(offset) = &self.noise_offset {
+            config.offset = [offset[0], offset[1]];
+        }
+        if let Some(rotation) = self.noise_rotation {
+            config.rotation = rotation;
+        }
+        if let Some(distance) = self.worley_distance {
+            config.worley.distance = distance;
+        }
+        if let Some(return_type) = self.worley_return {
+            config.worley.return_type = return_type;
+        }
+        Ok(config)
+    }
+
+    /// Build the noise graph if one was given, otherwise the resolved config, then warp it.
+    pub fn build(
+        &self,
+        preset: NoiseConfig,
+        seed: u32,
+    ) -> Result<Box<dyn NoiseFn<f64, 2> + Send + Sync>, LoadNoiseConfigError> {
+        let noise = match (&self.graph_file, &self.noise_graph) {
+            (Some(graph), _) => graph.build(seed),
+            (None, Some(path)) => NoiseNode::load(path)?.build(seed),
+            (None, None) => self.resolve(preset)?.build(seed),
+        };
+        Ok(self.warp(noise, seed, |config, seed| config.build(seed)))
+    }
+
+    /// Like [`NoiseConfigArgs::build`], but repeating every `period` units along x and y.
+    /// Noise graphs can't be tiled.
+    pub fn build_tileable(
+        &self,
+        preset: NoiseConfig,
+        seed: u32,
+        period: [f64; 2],
+    ) -> Result<Box<dyn NoiseFn<f64, 2> + Send + Sync>, LoadNoiseConfigError> {
+        if self.noise_graph.is_some() {
+            return Err(LoadNoiseConfigError::TileableGraph);
+        }
+        let noise = self.resolve(preset)?.build_tileable(seed, period);
+        // warped noise tiles as long as the warp fields do
+        Ok(self.warp(noise, seed, |config, seed| {
+            config.build_tileable(seed, period)
+        }))
+    }
+
+    /// A frame of an animation of [`NoiseConfigArgs::build`], at `time`.  Noise graphs
+    /// can't be animated.
+    pub fn build_frame(
+        &self,
+        preset: NoiseConfig,
+        seed: u32,
+        animation: &Animation,
+        time: f64,
+    ) -> Result<Box<dyn NoiseFn<f64, 2> + Send + Sync>, LoadNoiseConfigError> {
+        if self.noise_graph.is_some() {
+            return Err(LoadNoiseConfigError::AnimatedGraph);
+        }
+        let noise = self.resolve(preset)?.build_frame(seed, animation, time);
+        // the warp fields move with the noise, so warped animations loop too
+        Ok(self.warp(noise, seed, |config, seed| {
+            config.build_frame(seed, animation, time)
+        }))
+    }
+
+    /// Apply `--warp` to `noise`, if given, with fields made by `build`.  Warp fields are
+    /// seeded like the children of a graph's warp node.
+    fn warp(
+        &self,
+        noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+        seed: u32,
+        build: impl Fn(&NoiseConfig, u32) -> Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+    ) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        let Some(selector) = self.warp else {
+            return noise;
+        };
+        let config = NoiseConfig {
+            frequency: self.warp_frequency.unwrap_or(1.),
+            ..selector.config()
+        };
+        let mut warp = (0..self.warp_fields.unwrap_or(1).max(1) as u32).fold(
+            DomainWarp::new(noise),
+            |warp, i| {
+                warp.add_field(
+                    build(&config, child_seed(seed, 1 + 2 * i)),
+                    build(&config, child_seed(seed, 2 + 2 * i)),
+                )
+            },
+        );
+        if let Some(strength) = self.warp_strength {
+            warp = warp.set_strength(strength);
+        }
+        if let Some(iterations) = self.warp_iterations {
+            warp = warp.set_iterations(iterations);
+        }
+        Box::new(warp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn test_noise_config_from_toml() {
+        let config = NoiseConfig::from_toml(
+            r#"
+            base = "simplex"
+            fractal = "ridged-multi"
+            octaves = 6
+            frequency = 3.0
+            offset = [0.5, -2.0]
+
+            [worley]
+            distance = "manhattan"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            NoiseConfig {
+                base: BaseNoise::Simplex,
+                fractal: FractalType::RidgedMulti,
+                octaves: Some(6),
+                frequency: 3.,
+                offset: [0.5, -2.],
+                worley: WorleyOptions {
+                    distance: WorleyDistance::Manhattan,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        );
+        assert_eq!(NoiseConfig::from_toml(&config.to_toml()).unwrap(), config);
+        assert!(NoiseConfig::from_toml("octaves = 6\nfrequncy = 3.0").is_err());
+        assert!(NoiseConfig::from_toml("base = \"plaid\"").is_err());
+    }
+
+    #[test]
+    fn test_noise_config_transform() {
+        let plain = NoiseConfig::default();
+        let transformed = NoiseConfig {
+            frequency: 2.,
+            offset: [10., 20.],
+            rotation: 90.,
+            ..plain
+        };
+        let (a, b) = (plain.build(7), transformed.build(7));
+        // (1, 0) rotates to (0, 1), scales to (0, 2) and moves to (10, 22)
+        let expected = a.get([10., 22.]);
+        assert!((b.get([1., 0.]) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_noise_config_fractals_use_octaves() {
+        for fractal in FractalType::value_variants() {
+            let one = NoiseConfig {
+                fractal: *fractal,
+                octaves: Some(1),
+                ..Default::default()
+            };
+            let many = NoiseConfig {
+                octaves: Some(8),
+                ..one
+            };
+            let point = [0.3, 0.7];
+            let (a, b) = (one.build(3).get(point), many.build(3).get(point));
+            assert!(a.is_finite() && b.is_finite(), "{:?}", fractal);
+            if *fractal != FractalType::None {
+                assert_ne!(a, b, "{:?}", fractal);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tileable_render_edges_match() {
+        let (width, height) = (24, 16);
+        let period = [3., 2.];
+        let check = |noise: &dyn NoiseFn<f64, 2>, name: &str| {
+            // one extra row and column, which should wrap around to the first
+            let render: Vec<Vec<f64>> = (0..=height)
+                .map(|y| {
+                    (0..=width)
+                        .map(|x| {
+                            let x = x as f64 / width as f64 * period[0];
+                            let y = y as f64 / height as f64 * period[1];
+                            noise.get([x, y])
+                        })
+                        .collect()
+                })
+                .collect();
+            for row in &render {
+                assert!((row[0] - row[width]).abs() < 1e-9, "{}", name);
+            }
+            for (top, bottom) in render[0].iter().zip(&render[height]) {
+                assert!((top - bottom).abs() < 1e-9, "{}", name);
+            }
+            assert!(render[0].iter().any(|v| *v != render[0][0]), "{}", name);
+        };
+        for base in BaseNoise::value_variants() {
+            for fractal in [FractalType::None, FractalType::RidgedMulti] {
+                let config = NoiseConfig {
+                    base: *base,
+                    fractal,
+                    frequency: 2.,
+                    offset: [1., -4.],
+                    ..Default::default()
+                };
+                let name = format!("{:?} {:?}", base, fractal);
+                check(config.build_tileable(5, period).as_ref(), &name);
+            }
+        }
+
+        let args = TestArgs::parse_from(["test", "--warp", "perlin", "--warp-strength", "0.5"]);
+        let warped = args.noise.build_tileable(NoiseConfig::default(), 5, period);
+        check(warped.unwrap().as_ref(), "warped");
+    }
+
+    #[test]
+    fn test_animated_loop_returns_to_first_frame() {
+        let point = [0.3, 0.7];
+        let animation = Animation {
+            frames: 8,
+            period: 2.,
+            mode: TimeMode::Loop,
+        };
+        for base in BaseNoise::value_variants() {
+            let config = NoiseConfig {
+                base: *base,
+                fractal: FractalType::Fbm,
+                frequency: 3.,
+                rotation: 45.,
+                ..Default::default()
+            };
+            let noise = config.build_animated(4, &animation);
+            let first = noise.get([point[0], point[1], animation.time(0)]);
+            let next = noise.get([point[0], point[1], animation.time(1)]);
+            let last = noise.get([point[0], point[1], animation.time(8)]);
+            assert!((first - last).abs() < 1e-9, "{:?}", base);
+            assert_ne!(first, next, "{:?}", base);
+        }
+
+        let linear = Animation {
+            mode: TimeMode::Linear,
+            ..animation
+        };
+        let noise = NoiseConfig::default().build_animated(4, &linear);
+        assert_ne!(noise.get([0.3, 0.7, 0.]), noise.get([0.3, 0.7, 2.]));
+
+        let args = TestArgs::parse_from(["test", "--warp", "simplex"]);
+        let frame = |time| {
+            let noise = args
+                .noise
+                .build_frame(NoiseConfig::default(), 4, &animation, time);
+            noise.unwrap().get(point)
+        };
+        assert!((frame(0.) - frame(2.)).abs() < 1e-9);
+    }
+
+    #[derive(Parser)]
+    struct TestArgs {
+        #[command(flatten)]
+        noise: NoiseConfigArgs,
+    }
+
+    #[test]
+    fn test_noise_config_args_override_preset() {
+        let args = TestArgs::parse_from([
+            "test",
+            "--fractal",
+            "fbm",
+            "--octaves",
+            "3",
+            "--noise-offset",
+            "-1",
+            "2.5",
+        ]);
+        let preset = NoiseConfig {
+            base: BaseNoise::Value,
+            frequency: 4.,
+            ..Default::default()
+        };
+        let config = args.noise.resolve(preset).unwrap();
+        assert_eq!(config.base, BaseNoise::Value);
+        assert_eq!(config.fractal, FractalType::Fbm);
+        assert_eq!(config.octaves, Some(3));
+        assert_eq!(config.frequency, 4.);
+        assert_eq!(config.offset, [-1., 2.5]);
+    }
+
+    #[test]
+    fn test_noise_config_args_warp() {
+        let point = [0.3, 0.7];
+        let plain = TestArgs::parse_from(["test"]).noise;
+        let plain = plain.build(NoiseConfig::default(), 2).unwrap().get(point);
+        assert_eq!(plain, NoiseConfig::default().build(2).get(point));
+
+        let args = TestArgs::parse_from(["test", "--warp", "simplex", "--warp-iterations", "2"]);
+        let warped = args.noise.build(NoiseConfig::default(), 2).unwrap();
+        assert_ne!(warped.get(point), plain);
+        assert!(warped.get(point).is_finite());
+    }
+
+    #[test]
+    fn test_noise_config_args_load_files() {
+        let path = std::env::temp_dir().join("proc_art_test_load_files.toml");
+        fs::write(&path, "base = \"value\"\nfrequency = 2.0\n").unwrap();
+        let mut args = TestArgs::parse_from(["test", "--noise-config", path.to_str().unwrap()]);
+        args.noise.load_files().unwrap();
+        // later builds don't read the file again
+        fs::remove_file(&path).unwrap();
+        let config = args.noise.resolve(NoiseConfig::default()).unwrap();
+        assert_eq!(config.base, BaseNoise::Value);
+        assert_eq!(config.frequency, 2.);
+        assert!(args.noise.build(NoiseConfig::default(), 1).is_ok());
+    }
+
+    #[test]
+    fn test_noise_config_args_reject_bad_numbers() {
+        for (flag, value) in [
+            ("--frequency", "0"),
+            ("--frequency", "inf"),
+            ("--lacunarity", "NaN"),
+            ("--persistence", "-inf"),
+        ] {
+            assert!(TestArgs::try_parse_from(["test", flag, value]).is_err());
+        }
+        assert!(TestArgs::try_parse_from(["test", "--frequency", "0.5"]).is_ok());
+    }
+}
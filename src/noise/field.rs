@@ -0,0 +1,129 @@
+//! 2D vector fields built from noise.
+//!
+//! Two independent noise channels make a field full of sinks and sources,
+//! where flow lines pile up or spread apart.  The curl of a single noise
+//! function, `(dn/dy, -dn/dx)`, is divergence free instead: flow lines follow
+//! the contours of the noise and never converge.
+
+use na::Vector2;
+use noise::NoiseFn;
+
+use super::WarpField;
+use crate::flow::FlowField;
+
+/// Two noise channels, one per axis.
+impl FlowField for WarpField {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        let point = pos.into();
+        Vector2::new(self.x.get(point), self.y.get(point))
+    }
+}
+
+/// The gradient of a scalar 2D function, `[dn/dx, dn/dy]`.
+///
+/// The noise crate's own simplex derivatives are wrong in the version we use,
+/// so [`FiniteDifference`] is the only implementation for now.
+pub trait Gradient {
+    fn gradient(&self, point: [f64; 2]) -> [f64; 2];
+}
+
+/// The gradient of any noise function by central differences.
+pub struct FiniteDifference<Source> {
+    pub source: Source,
+    /// Distance between the sampled points and the point itself.
+    pub epsilon: f64,
+}
+
+impl<Source> FiniteDifference<Source> {
+    pub const DEFAULT_EPSILON: f64 = 1e-4;
+
+    pub fn new(source: Source) -> Self {
+        FiniteDifference {
+            source,
+            epsilon: Self::DEFAULT_EPSILON,
+        }
+    }
+
+    pub fn set_epsilon(self, epsilon: f64) -> Self {
+        FiniteDifference { epsilon, ..self }
+    }
+}
+
+impl<Source: NoiseFn<f64, 2>> Gradient for FiniteDifference<Source> {
+    fn gradient(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let e = self.epsilon;
+        let dx = self.source.get([x + e, y]) - self.source.get([x - e, y]);
+        let dy = self.source.get([x, y + e]) - self.source.get([x, y - e]);
+        [dx / (2. * e), dy / (2. * e)]
+    }
+}
+
+/// The curl of a scalar field, its gradient turned a quarter turn clockwise.
+pub struct Curl<Source> {
+    pub source: Source,
+}
+
+impl<Source> Curl<Source> {
+    pub fn new(source: Source) -> Self {
+        Curl { source }
+    }
+}
+
+impl<Source: Gradient> FlowField for Curl<Source> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        let [dx, dy] = self.source.gradient(pos.into());
+        Vector2::new(dy, -dx)
+    }
+}
+
+/// The divergence of `field` at `pos`, by central differences `epsilon` apart.
+pub fn divergence(field: &(impl FlowField + ?Sized), pos: Vector2<f64>, epsilon: f64) -> f64 {
+    let (ex, ey) = (Vector2::new(epsilon, 0.), Vector2::new(0., epsilon));
+    let dx = field.sample(pos + ex).x - field.sample(pos - ex).x;
+    let dy = field.sample(pos + ey).y - field.sample(pos - ey).y;
+    (dx + dy) / (2. * epsilon)
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::{Fbm, Perlin};
+
+    use super::*;
+
+    #[test]
+    fn test_finite_difference_gradient() {
+        // n = x^2 + 3y, so the gradient is (2x, 3)
+        let gradient = FiniteDifference::new(Square).set_epsilon(1e-3);
+        let [dx, dy] = gradient.gradient([1.5, -2.]);
+        assert!((dx - 3.).abs() < 1e-9 && (dy - 3.).abs() < 1e-9);
+        let curl = Curl::new(gradient).sample(Vector2::new(0.5, 0.));
+        assert!((curl.x - 3.).abs() < 1e-9 && (curl.y + 1.).abs() < 1e-9);
+    }
+
+    /// Returns `x^2 + 3y`.
+    struct Square;
+
+    impl NoiseFn<f64, 2> for Square {
+        fn get(&self, [x, y]: [f64; 2]) -> f64 {
+            x * x + 3. * y
+        }
+    }
+
+    #[test]
+    fn test_curl_is_divergence_free() {
+        let epsilon = 1e-3;
+        let curl = Curl::new(FiniteDifference::new(Fbm::<Perlin>::new(3)).set_epsilon(epsilon));
+        let channels = WarpField {
+            x: Box::new(Perlin::new(3)),
+            y: Box::new(Perlin::new(4)),
+        };
+        // differences the same distance apart cancel exactly, up to rounding
+        let points = [[0.3, 0.7], [1.9, -2.4], [-4.5, 0.1], [2.2, 3.3]].map(Vector2::from);
+        for point in points {
+            assert!(divergence(&curl, point, epsilon).abs() < 1e-6);
+        }
+        assert!(points
+            .iter()
+            .any(|p| divergence(&channels, *p, epsilon).abs() > 0.1));
+    }
+}
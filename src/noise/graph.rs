@@ -0,0 +1,580 @@
+//! Noise functions composed from a tree of generators and combinators.
+//!
+//! A graph is read from a TOML or RON file.  Every node has a `type`, and
+//! combinators nest their inputs as child nodes, e.g.
+//!
+//! ```toml
+//! type = "select"
+//! bounds = [0.0, 1.0]
+//! falloff = 0.1
+//!
+//! [a]
+//! type = "noise"
+//! base = "simplex"
+//! frequency = 4.0
+//!
+//! [b]
+//! type = "scale-bias"
+//! scale = 0.5
+//! source = { type = "noise", fractal = "ridged-multi" }
+//!
+//! [control]
+//! type = "noise"
+//! base = "value"
+//! ```
+//!
+//! The graph is built from a single master seed.  Each child is seeded from
+//! its parent's seed and its position under the parent, so identical
+//! subtrees in different places produce different noise.
+
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use noise::{
+    Add, Blend, Constant, Curve, Displace, Multiply, NoiseFn, Perlin, ScaleBias, Seedable, Select,
+    Terrace, Turbulence,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{DomainWarp, NoiseConfig};
+
+fn one() -> f64 {
+    1.
+}
+
+fn unit_bounds() -> [f64; 2] {
+    [0., 1.]
+}
+
+fn default_roughness() -> usize {
+    3
+}
+
+fn one_iteration() -> usize {
+    1
+}
+
+/// A node in a noise graph.  Leaves generate noise, other nodes combine or modify their inputs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum NoiseNode {
+    /// A noise function, with the same fields as a [`NoiseConfig`] file.
+    Noise(NoiseConfig),
+    Constant {
+        value: f64,
+    },
+    /// Sum of all sources.
+    Add {
+        sources: Vec<NoiseNode>,
+    },
+    /// Product of all sources.
+    Multiply {
+        sources: Vec<NoiseNode>,
+    },
+    /// `a` where `control` is outside `bounds`, `b` inside, blended across `falloff`.
+    Select {
+        a: Box<NoiseNode>,
+        b: Box<NoiseNode>,
+        control: Box<NoiseNode>,
+        #[serde(default = "unit_bounds")]
+        bounds: [f64; 2],
+        #[serde(default)]
+        falloff: f64,
+    },
+    /// Linear interpolation from `a` to `b`, weighted by `control`.
+    Blend {
+        a: Box<NoiseNode>,
+        b: Box<NoiseNode>,
+        control: Box<NoiseNode>,
+    },
+    /// Jitter input points by `power` with fBm of `roughness` octaves.
+    Turbulence {
+        source: Box<NoiseNode>,
+        #[serde(default = "one")]
+        frequency: f64,
+        #[serde(default = "one")]
+        power: f64,
+        #[serde(default = "default_roughness")]
+        roughness: usize,
+    },
+    /// Offset input points by the values of the `x` and `y` nodes.
+    Displace {
+        source: Box<NoiseNode>,
+        x: Box<NoiseNode>,
+        y: Box<NoiseNode>,
+    },
+    /// Remap values through a spline of at least 4 `[input, output]` points with distinct inputs.
+    Curve {
+        source: Box<NoiseNode>,
+        points: Vec<[f64; 2]>,
+    },
+    /// Flatten values into terraces between at least 2 distinct points.
+    Terrace {
+        source: Box<NoiseNode>,
+        points: Vec<f64>,
+        #[serde(default)]
+        invert: bool,
+    },
+    /// `source * scale + bias`
+    ScaleBias {
+        source: Box<NoiseNode>,
+        #[serde(default = "one")]
+        scale: f64,
+        #[serde(default)]
+        bias: f64,
+    },
+    /// Sample `source` at points pushed by `strength` times the warp fields, see [`DomainWarp`].
+    Warp {
+        source: Box<NoiseNode>,
+        fields: Vec<WarpFieldNode>,
+        #[serde(default = "one")]
+        strength: f64,
+        #[serde(default = "one_iteration")]
+        iterations: usize,
+    },
+}
+
+/// The x and y offsets of a domain warp field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WarpFieldNode {
+    pub x: NoiseNode,
+    pub y: NoiseNode,
+}
+
+/// Seed for the `index`th child of a node seeded `seed`.
+pub(super) fn child_seed(seed: u32, index: u32) -> u32 {
+    // splitmix64 finalizer
+    let mut x = ((seed as u64) << 32 | index as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (x ^ (x >> 31)) as u32
+}
+
+/// Number of control points `Curve` and `Terrace` keep, since they drop any point within
+/// `f64::EPSILON` of one they already have.
+fn distinct_inputs(inputs: impl Iterator<Item = f64>) -> usize {
+    let mut kept: Vec<f64> = Vec::new();
+    for input in inputs {
+        if !kept.iter().any(|k| (k - input).abs() < f64::EPSILON) {
+            kept.push(input);
+        }
+    }
+    kept.len()
+}
+
+fn combine<F, N>(
+    sources: &[NoiseNode],
+    seed: u32,
+    combinator: F,
+) -> Box<dyn NoiseFn<f64, 2> + Send + Sync>
+where
+    F: Fn(Box<dyn NoiseFn<f64, 2> + Send + Sync>, Box<dyn NoiseFn<f64, 2> + Send + Sync>) -> N,
+    N: NoiseFn<f64, 2> + Send + Sync + 'static,
+{
+    sources
+        .iter()
+        .enumerate()
+        .map(|(i, node)| node.build(child_seed(seed, i as u32)))
+        .reduce(|a, b| Box::new(combinator(a, b)))
+        .expect("graph was validated")
+}
+
+impl NoiseNode {
+    /// Parse a graph from TOML and check that it can be built.
+    pub fn from_toml(s: &str) -> Result<Self, LoadNoiseGraphError> {
+        let node: Self = toml::from_str(s)?;
+        node.validate()?;
+        Ok(node)
+    }
+
+    /// Parse a graph from RON and check that it can be built.
+    pub fn from_ron(s: &str) -> Result<Self, LoadNoiseGraphError> {
+        let node: Self = ron::from_str(s)?;
+        node.validate()?;
+        Ok(node)
+    }
+
+    /// Load a graph from a `.ron` file, or from TOML for any other extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadNoiseGraphError> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path)?;
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("ron") => Self::from_ron(&s),
+            _ => Self::from_toml(&s),
+        }
+    }
+
+    /// Check the inputs of every node, which the noise functions would otherwise panic on.
+    pub fn validate(&self) -> Result<(), LoadNoiseGraphError> {
+        let too_few = |node: &str, what: &str, needed: usize, found: usize| {
+            if found < needed {
+                Err(LoadNoiseGraphError::Invalid(format!(
+                    "{} needs at least {} {}, found {}",
+                    node, needed, what, found
+                )))
+            } else {
+                Ok(())
+            }
+        };
+        match self {
+            Self::Noise(_) | Self::Constant { .. } => Ok(()),
+            Self::Add { sources } | Self::Multiply { sources } => {
+                let node = if matches!(self, Self::Add { .. }) {
+                    "add"
+                } else {
+                    "multiply"
+                };
+                too_few(node, "sources", 1, sources.len())?;
+                sources.iter().try_for_each(Self::validate)
+            }
+            Self::Select { a, b, control, .. }
+            | Self::Blend { a, b, control }
+            | Self::Displace {
+                source: a,
+                x: b,
+                y: control,
+            } => {
+                a.validate()?;
+                b.validate()?;
+                control.validate()
+            }
+            Self::Turbulence { source, .. } | Self::ScaleBias { source, .. } => source.validate(),
+            Self::Curve { source, points } => {
+                let inputs = distinct_inputs(points.iter().map(|p| p[0]));
+                too_few("curve", "points with distinct inputs", 4, inputs)?;
+                source.validate()
+            }
+            Self::Terrace { source, points, .. } => {
+                too_few(
+                    "terrace",
+                    "distinct points",
+                    2,
+                    distinct_inputs(points.iter().copied()),
+                )?;
+                source.validate()
+            }
+            Self::Warp { source, fields, .. } => {
+                too_few("warp", "fields", 1, fields.len())?;
+                for field in fields {
+                    field.x.validate()?;
+                    field.y.validate()?;
+                }
+                source.validate()
+            }
+        }
+    }
+
+    /// Build the noise function.  Children are seeded by hashing `seed` with their position.
+    ///
+    /// Panics if the graph fails [`NoiseNode::validate`].
+    pub fn build(&self, seed: u32) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        let child = |node: &NoiseNode, index| node.build(child_seed(seed, index));
+        match self {
+            Self::Noise(config) => config.build(seed),
+            Self::Constant { value } => Box::new(Constant::new(*value)),
+            Self::Add { sources } => combine(sources, seed, Add::new),
+            Self::Multiply { sources } => combine(sources, seed, Multiply::new),
+            Self::Select {
+                a,
+                b,
+                control,
+                bounds,
+                falloff,
+            } => Box::new(
+                Select::new(child(a, 0), child(b, 1), child(control, 2))
+                    .set_bounds(bounds[0], bounds[1])
+                    .set_falloff(*falloff),
+            ),
+            Self::Blend { a, b, control } => {
+                Box::new(Blend::new(child(a, 0), child(b, 1), child(control, 2)))
+            }
+            Self::Turbulence {
+                source,
+                frequency,
+                power,
+                roughness,
+            } => Box::new(
+                Turbulence::<_, Perlin>::new(child(source, 0))
+                    .set_seed(child_seed(seed, 1))
+                    .set_frequency(*frequency)
+                    .set_power(*power)
+                    .set_roughness(*roughness),
+            ),
+            // z and u displacements are unused in 2D
+            Self::Displace { source, x, y } => Box::new(Displace::new(
+                child(source, 0),
+                child(x, 1),
+                child(y, 2),
+                Constant::new(0.),
+                Constant::new(0.),
+            )),
+            Self::Curve { source, points } => Box::new(
+                points
+                    .iter()
+                    .fold(Curve::new(child(source, 0)), |curve, [input, output]| {
+                        curve.add_control_point(*input, *output)
+                    }),
+            ),
+            Self::Terrace {
+                source,
+                points,
+                invert,
+            } => Box::new(
+                points
+                    .iter()
+                    .fold(Terrace::new(child(source, 0)), |terrace, point| {
+                        terrace.add_control_point(*point)
+                    })
+                    .invert_terraces(*invert),
+            ),
+            Self::ScaleBias {
+                source,
+                scale,
+                bias,
+            } => Box::new(
+                ScaleBias::new(child(source, 0))
+                    .set_scale(*scale)
+                    .set_bias(*bias),
+            ),
+            Self::Warp {
+                source,
+                fields,
+                strength,
+                iterations,
+            } => Box::new(
+                fields
+                    .iter()
+                    .enumerate()
+                    .fold(DomainWarp::new(child(source, 0)), |warp, (i, field)| {
+                        let i = i as u32;
+                        warp.add_field(child(&field.x, 1 + 2 * i), child(&field.y, 2 + 2 * i))
+                    })
+                    .set_strength(*strength)
+                    .set_iterations(*iterations),
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadNoiseGraphError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Ron(ron::error::SpannedError),
+    /// A node that would fail to build, e.g. a curve with too few points.
+    Invalid(String),
+}
+
+impl fmt::Display for LoadNoiseGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read noise graph: {}", e),
+            Self::Toml(e) => write!(f, "could not parse noise graph: {}", e),
+            Self::Ron(e) => write!(f, "could not parse noise graph: {}", e),
+            Self::Invalid(msg) => write!(f, "invalid noise graph: {}", msg),
+        }
+    }
+}
+
+impl Error for LoadNoiseGraphError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Toml(e) => Some(e),
+            Self::Ron(e) => Some(e),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadNoiseGraphError {
+    fn from(value: io::Error) -> Self {
+        LoadNoiseGraphError::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for LoadNoiseGraphError {
+    fn from(value: toml::de::Error) -> Self {
+        LoadNoiseGraphError::Toml(value)
+    }
+}
+
+impl From<ron::error::SpannedError> for LoadNoiseGraphError {
+    fn from(value: ron::error::SpannedError) -> Self {
+        LoadNoiseGraphError::Ron(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::{BaseNoise, FractalType};
+
+    fn noise(base: BaseNoise) -> Box<NoiseNode> {
+        Box::new(NoiseNode::Noise(NoiseConfig {
+            base,
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn test_noise_graph_from_toml_and_ron() {
+        let toml = r#"
+            type = "select"
+            falloff = 0.1
+
+            [a]
+            type = "noise"
+            base = "simplex"
+
+            [b]
+            type = "scale-bias"
+            scale = 0.5
+            source = { type = "noise", fractal = "ridged-multi" }
+
+            [control]
+            type = "constant"
+            value = 0.25
+        "#;
+        let ron = r#"(
+            type: "select",
+            falloff: 0.1,
+            a: (type: "noise", base: "simplex"),
+            b: (
+                type: "scale-bias",
+                scale: 0.5,
+                source: (type: "noise", fractal: "ridged-multi"),
+            ),
+            control: (type: "constant", value: 0.25),
+        )"#;
+        let expected = NoiseNode::Select {
+            a: noise(BaseNoise::Simplex),
+            b: Box::new(NoiseNode::ScaleBias {
+                source: Box::new(NoiseNode::Noise(NoiseConfig {
+                    fractal: FractalType::RidgedMulti,
+                    ..Default::default()
+                })),
+                scale: 0.5,
+                bias: 0.,
+            }),
+            control: Box::new(NoiseNode::Constant { value: 0.25 }),
+            bounds: [0., 1.],
+            falloff: 0.1,
+        };
+        assert_eq!(NoiseNode::from_toml(toml).unwrap(), expected);
+        assert_eq!(NoiseNode::from_ron(ron).unwrap(), expected);
+
+        assert!(NoiseNode::from_toml("type = \"swirl\"").is_err());
+        assert!(NoiseNode::from_toml("type = \"constant\"\nvalue = 1\nscale = 2").is_err());
+        assert!(matches!(
+            NoiseNode::from_toml("type = \"add\"\nsources = []"),
+            Err(LoadNoiseGraphError::Invalid(_))
+        ));
+        assert!(matches!(
+            NoiseNode::from_ron(
+                r#"(type: "curve", points: [[0, 0], [1, 1]], source: (type: "noise"))"#
+            ),
+            Err(LoadNoiseGraphError::Invalid(_))
+        ));
+        // the noise library drops repeated inputs, then panics on too few points
+        assert!(matches!(
+            NoiseNode::from_ron(
+                r#"(type: "curve", points: [[0, 0], [0, 1], [0, 2], [1, 1]], source: (type: "noise"))"#
+            ),
+            Err(LoadNoiseGraphError::Invalid(_))
+        ));
+        assert!(matches!(
+            NoiseNode::from_toml(
+                "type = \"terrace\"\npoints = [0.5, 0.5]\nsource = { type = \"noise\" }"
+            ),
+            Err(LoadNoiseGraphError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_example_graphs_load() {
+        for path in [
+            "assets/noise/terraced-hills.toml",
+            "assets/noise/marble.ron",
+        ] {
+            let graph = NoiseNode::load(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            assert!(graph.build(1).get([0.3, 0.7]).is_finite(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_noise_graph_seeds() {
+        let leaf = NoiseNode::Noise(NoiseConfig::default());
+        let point = [0.3, 0.7];
+        // a lone noise node uses the master seed directly
+        assert_eq!(
+            leaf.build(5).get(point),
+            NoiseConfig::default().build(5).get(point)
+        );
+
+        // identical children are seeded differently
+        let sum = NoiseNode::Add {
+            sources: vec![leaf.clone(), leaf.clone()],
+        };
+        let sum = sum.build(5).get(point);
+        let doubled = 2. * leaf.build(child_seed(5, 0)).get(point);
+        assert_ne!(sum, doubled);
+        assert_ne!(child_seed(5, 0), child_seed(5, 1));
+        assert_ne!(child_seed(5, 0), child_seed(6, 0));
+    }
+
+    #[test]
+    fn test_noise_graph_combinators() {
+        let constant = |value| Box::new(NoiseNode::Constant { value });
+        let point = [0.3, 0.7];
+        let product = NoiseNode::Multiply {
+            sources: vec![*constant(0.5), *constant(-3.)],
+        };
+        assert_eq!(product.build(0).get(point), -1.5);
+
+        let scaled = NoiseNode::ScaleBias {
+            source: Box::new(product),
+            scale: 2.,
+            bias: 1.,
+        };
+        assert_eq!(scaled.build(0).get(point), -2.);
+
+        let blend = NoiseNode::Blend {
+            a: constant(0.),
+            b: constant(1.),
+            control: constant(0.25),
+        };
+        assert_eq!(blend.build(0).get(point), 0.25);
+
+        // displacing by a constant shifts the source
+        let displaced = NoiseNode::Displace {
+            source: noise(BaseNoise::Perlin),
+            x: constant(1.),
+            y: constant(2.),
+        };
+        let source = noise(BaseNoise::Perlin).build(child_seed(9, 0));
+        assert_eq!(displaced.build(9).get(point), source.get([1.3, 2.7]));
+
+        let warp = NoiseNode::from_toml(
+            r#"
+            type = "warp"
+            strength = 0.5
+            source = { type = "noise" }
+            fields = [{ x = { type = "constant", value = 2.0 }, y = { type = "constant", value = 4.0 } }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(warp.build(9).get(point), source.get([1.3, 2.7]));
+
+        let terrace = NoiseNode::Terrace {
+            source: noise(BaseNoise::Simplex),
+            points: vec![-1., 0., 1.],
+            invert: false,
+        };
+        let terrace = terrace.build(1);
+        for x in 0..20 {
+            let v = terrace.get([x as f64 * 0.17, 0.5]);
+            assert!((-1. ..=1.).contains(&v), "{}", v);
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! Noise and vector fields sampled once onto a grid, then interpolated.
+//!
+//! Sampling is done in parallel, and grids can be saved and loaded, so a
+//! drawing can be iterated on without evaluating noise again.  Grids are saved
+//! losslessly in a small binary format, or as EXR images for other tools.
+
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use image::{ImageError, Rgb32FImage};
+use na::Vector2;
+use noise::NoiseFn;
+use rayon::prelude::*;
+
+use crate::flow::FlowField;
+
+/// First bytes of a saved grid.
+const MAGIC: &[u8; 8] = b"PAFIELD1";
+
+/// How values between grid points are found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GridInterpolation {
+    /// Linear along each axis, from the 4 nearest grid points.
+    #[default]
+    Bilinear,
+    /// Catmull-Rom splines along each axis, from the 16 nearest grid points.  Smooth
+    /// across grid lines.
+    Bicubic,
+}
+
+impl GridInterpolation {
+    /// Grid indices and weights along one axis for the grid coordinate `g`, on an
+    /// axis of `n` points.  Bicubic interpolation extrapolates linearly past the
+    /// ends of the axis, so linear functions are reproduced exactly up to the edges.
+    fn taps(&self, g: f64, n: usize) -> [(usize, f64); 4] {
+        let g = g.clamp(0., (n - 1) as f64);
+        let i = (g.floor() as usize).min(n.saturating_sub(2));
+        let t = g - i as f64;
+        let at = |k: isize| (i as isize + k).clamp(0, n as isize - 1) as usize;
+        match self {
+            Self::Bilinear => [(at(0), 1. - t), (at(1), t), (at(0), 0.), (at(0), 0.)],
+            Self::Bicubic => {
+                let (t2, t3) = (t * t, t * t * t);
+                let mut w = [
+                    (-t3 + 2. * t2 - t) / 2.,
+                    (3. * t3 - 5. * t2 + 2.) / 2.,
+                    (-3. * t3 + 4. * t2 + t) / 2.,
+                    (t3 - t2) / 2.,
+                ];
+                // a point past the edge is the edge plus the step into it
+                if i == 0 {
+                    w = [0., w[1] + 2. * w[0], w[2] - w[0], w[3]];
+                }
+                if i + 2 >= n {
+                    w = [w[0], w[1] - w[3], w[2] + 2. * w[3], 0.];
+                }
+                [(at(-1), w[0]), (at(0), w[1]), (at(1), w[2]), (at(2), w[3])]
+            }
+        }
+    }
+}
+
+/// A grid of `CHANNELS` values per point, covering `size` units from `origin`.
+///
+/// Grid points lie on the edges of the covered area as well as inside it.
+/// One-channel grids are noise functions and two-channel grids are vector
+/// fields.  Points outside the area take the value at the nearest edge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledField<const CHANNELS: usize> {
+    pub origin: [f64; 2],
+    pub size: [f64; 2],
+    /// Number of grid points along x and y.
+    pub resolution: [usize; 2],
+    pub interpolation: GridInterpolation,
+    /// Values row by row, starting at `origin`.
+    values: Vec<[f64; CHANNELS]>,
+}
+
+impl SampledField<1> {
+    pub fn from_noise<N>(
+        noise: &N,
+        origin: [f64; 2],
+        size: [f64; 2],
+        resolution: [usize; 2],
+    ) -> Self
+    where
+        N: NoiseFn<f64, 2> + Sync + ?Sized,
+    {
+        Self::sample(origin, size, resolution, |point| [noise.get(point)])
+    }
+}
+
+impl SampledField<2> {
+    pub fn from_field<F>(
+        field: &F,
+        origin: [f64; 2],
+        size: [f64; 2],
+        resolution: [usize; 2],
+    ) -> Self
+    where
+        F: FlowField + Sync + ?Sized,
+    {
+        Self::sample(origin, size, resolution, |point| {
+            field.sample(point.into()).into()
+        })
+    }
+}
+
+impl<const CHANNELS: usize> SampledField<CHANNELS> {
+    /// Sample `f` at every grid point, in parallel.  Resolutions are at least 1.
+    pub fn sample<F>(origin: [f64; 2], size: [f64; 2], resolution: [usize; 2], f: F) -> Self
+    where
+        F: Fn([f64; 2]) -> [f64; CHANNELS] + Sync,
+    {
+        let resolution = resolution.map(|n| n.max(1));
+        let mut field = SampledField {
+            origin,
+            size,
+            resolution,
+            interpolation: GridInterpolation::default(),
+            values: vec![],
+        };
+        let [nx, ny] = resolution;
+        field.values = (0..nx * ny)
+            .into_par_iter()
+            .map(|i| f(field.grid_point(i % nx, i / nx)))
+            .collect();
+        field
+    }
+
+    pub fn set_interpolation(self, interpolation: GridInterpolation) -> Self {
+        SampledField {
+            interpolation,
+            ..self
+        }
+    }
+
+    /// Position of the grid point in column `i` and row `j`.
+    fn grid_point(&self, i: usize, j: usize) -> [f64; 2] {
+        let step = |axis: usize, k: usize| {
+            let n = self.resolution[axis];
+            if n > 1 {
+                self.size[axis] * k as f64 / (n - 1) as f64
+            } else {
+                0.
+            }
+        };
+        [self.origin[0] + step(0, i), self.origin[1] + step(1, j)]
+    }
+
+    /// The interpolated values at `point`.
+    pub fn lookup(&self, [x, y]: [f64; 2]) -> [f64; CHANNELS] {
+        let [nx, ny] = self.resolution;
+        let grid = |v: f64, axis: usize| {
+            let extent = self.size[axis];
+            if extent == 0. {
+                return 0.;
+            }
+            (v - self.origin[axis]) / extent * (self.resolution[axis] - 1) as f64
+        };
+        let xs = self.interpolation.taps(grid(x, 0), nx);
+        let ys = self.interpolation.taps(grid(y, 1), ny);
+        let mut out = [0.; CHANNELS];
+        for (j, wy) in ys {
+            for (i, wx) in xs {
+                let w = wx * wy;
+                if w == 0. {
+                    continue;
+                }
+                for (o, v) in out.iter_mut().zip(self.values[j * nx + i]) {
+                    *o += w * v;
+                }
+            }
+        }
+        out
+    }
+
+    /// Save the grid losslessly, in a binary format read by [`SampledField::load`].
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((CHANNELS as u32).to_le_bytes());
+        for n in self.resolution {
+            bytes.extend((n as u64).to_le_bytes());
+        }
+        for v in self.origin.iter().chain(&self.size) {
+            bytes.extend(v.to_le_bytes());
+        }
+        for v in self.values.iter().flatten() {
+            bytes.extend(v.to_le_bytes());
+        }
+        fs::File::create(path)?.write_all(&bytes)
+    }
+
+    /// Load a grid saved by [`SampledField::save`], with bilinear interpolation.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadSampledFieldError> {
+        let mut bytes = vec![];
+        fs::File::open(path)?.read_to_end(&mut bytes)?;
+        let mut bytes = bytes.as_slice();
+        if take(&mut bytes)? != *MAGIC {
+            return Err(LoadSampledFieldError::Format("not a sampled field".into()));
+        }
+        let channels = u32::from_le_bytes(take(&mut bytes)?) as usize;
+        if channels != CHANNELS {
+            return Err(LoadSampledFieldError::Format(format!(
+                "expected {} channels, found {}",
+                CHANNELS, channels
+            )));
+        }
+        let mut next_u64 = || take(&mut bytes).map(u64::from_le_bytes);
+        let resolution = [next_u64()? as usize, next_u64()? as usize];
+        check_resolution(resolution)?;
+        let mut next_f64 = || take(&mut bytes).map(f64::from_le_bytes);
+        let origin = [next_f64()?, next_f64()?];
+        let size = [next_f64()?, next_f64()?];
+        let n_values = resolution[0]
+            .checked_mul(resolution[1])
+            .ok_or_else(|| LoadSampledFieldError::Format("grid is too large".into()))?;
+        let values = (0..n_values)
+            .map(|_| {
+                let mut value = [0.; CHANNELS];
+                for v in &mut value {
+                    *v = f64::from_le_bytes(take(&mut bytes)?);
+                }
+                Ok(value)
+            })
+            .collect::<Result<_, LoadSampledFieldError>>()?;
+        if !bytes.is_empty() {
+            return Err(LoadSampledFieldError::Format("file is too long".into()));
+        }
+        Ok(SampledField {
+            origin,
+            size,
+            resolution,
+            interpolation: GridInterpolation::default(),
+            values,
+        })
+    }
+
+    /// Save the grid as an EXR image, one pixel per grid point with channels in
+    /// red, green and blue.  Values are stored as 32-bit floats, and the covered
+    /// area isn't stored at all.
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        let [nx, ny] = self.resolution;
+        let image = Rgb32FImage::from_fn(nx as u32, ny as u32, |i, j| {
+            let value = self.values[j as usize * nx + i as usize];
+            let mut rgb = [0.; 3];
+            for (c, v) in rgb.iter_mut().zip(value) {
+                *c = v as f32;
+            }
+            image::Rgb(rgb)
+        });
+        image.save(path)
+    }
+
+    /// Load an EXR image saved by [`SampledField::save_exr`], covering `size` units
+    /// from `origin`.
+    pub fn load_exr(
+        path: impl AsRef<Path>,
+        origin: [f64; 2],
+        size: [f64; 2],
+    ) -> Result<Self, LoadSampledFieldError> {
+        if CHANNELS > 3 {
+            return Err(LoadSampledFieldError::Format(format!(
+                "EXR images hold at most 3 channels, not {}",
+                CHANNELS
+            )));
+        }
+        let image = image::open(path)?.into_rgb32f();
+        let resolution = [image.width() as usize, image.height() as usize];
+        check_resolution(resolution)?;
+        let values = image
+            .pixels()
+            .map(|pixel| {
+                let mut value = [0.; CHANNELS];
+                for (v, c) in value.iter_mut().zip(pixel.0) {
+                    *v = c as f64;
+                }
+                value
+            })
+            .collect();
+        Ok(SampledField {
+            origin,
+            size,
+            resolution,
+            interpolation: GridInterpolation::default(),
+            values,
+        })
+    }
+}
+
+/// Grids need at least one point along each axis.
+fn check_resolution(resolution: [usize; 2]) -> Result<(), LoadSampledFieldError> {
+    if resolution.contains(&0) {
+        return Err(LoadSampledFieldError::Format(format!(
+            "grid has no points, resolution {}x{}",
+            resolution[0], resolution[1]
+        )));
+    }
+    Ok(())
+}
+
+/// Split the first `N` bytes off `bytes`.
+fn take<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], LoadSampledFieldError> {
+    if bytes.len() < N {
+        return Err(LoadSampledFieldError::Format("file is truncated".into()));
+    }
+    let (head, rest) = bytes.split_at(N);
+    *bytes = rest;
+    Ok(head.try_into().unwrap())
+}
+
+impl NoiseFn<f64, 2> for SampledField<1> {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        self.lookup(point)[0]
+    }
+}
+
+impl FlowField for SampledField<2> {
+    fn sample(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        Vector2::from(self.lookup(pos.into()))
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadSampledFieldError {
+    Io(io::Error),
+    Image(ImageError),
+    /// The file isn't a grid with the expected number of channels.
+    Format(String),
+}
+
+impl fmt::Display for LoadSampledFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read sampled field: {}", e),
+            Self::Image(e) => write!(f, "could not read sampled field: {}", e),
+            Self::Format(msg) => write!(f, "invalid sampled field: {}", msg),
+        }
+    }
+}
+
+impl Error for LoadSampledFieldError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Image(e) => Some(e),
+            Self::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadSampledFieldError {
+    fn from(value: io::Error) -> Self {
+        LoadSampledFieldError::Io(value)
+    }
+}
+
+impl From<ImageError> for LoadSampledFieldError {
+    fn from(value: ImageError) -> Self {
+        LoadSampledFieldError::Image(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use noise::Perlin;
+
+    use super::*;
+
+    /// Returns `2x - y + 1`.
+    struct Plane;
+
+    impl NoiseFn<f64, 2> for Plane {
+        fn get(&self, [x, y]: [f64; 2]) -> f64 {
+            2. * x - y + 1.
+        }
+    }
+
+    #[test]
+    fn test_sampled_field_interpolates() {
+        let plane = SampledField::from_noise(&Plane, [-1., 0.], [4., 2.], [5, 3]);
+        for interpolation in GridInterpolation::value_variants() {
+            let plane = plane.clone().set_interpolation(*interpolation);
+            for point in [[0.3, 0.7], [-1., 2.], [2.5, 1.25]] {
+                assert!((plane.get(point) - Plane.get(point)).abs() < 1e-9);
+            }
+            // clamped to the nearest edge outside the grid
+            assert!((plane.get([5., -1.]) - Plane.get([3., 0.])).abs() < 1e-9);
+        }
+
+        let perlin = Perlin::new(2);
+        let bilinear = SampledField::from_noise(&perlin, [0., 0.], [4., 4.], [65, 65]);
+        let bicubic = bilinear
+            .clone()
+            .set_interpolation(GridInterpolation::Bicubic);
+        for point in [[0.3, 0.7], [1.9, 2.4], [3.51, 0.1]] {
+            assert!((bilinear.get(point) - perlin.get(point)).abs() < 0.02);
+            assert!((bicubic.get(point) - perlin.get(point)).abs() < 0.005);
+        }
+    }
+
+    #[test]
+    fn test_sampled_field_save_load() {
+        let field = SampledField::sample([1., 2.], [3., 4.], [6, 5], |[x, y]| [x * y, x - y]);
+        let dir = env::temp_dir();
+
+        let path = dir.join("proc_art_test_sampled_field.bin");
+        field.save(&path).unwrap();
+        assert_eq!(SampledField::<2>::load(&path).unwrap(), field);
+        assert!(SampledField::<1>::load(&path).is_err());
+
+        // an empty grid would panic on lookup
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[12..20].copy_from_slice(&0_u64.to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(matches!(
+            SampledField::<2>::load(&path),
+            Err(LoadSampledFieldError::Format(_))
+        ));
+
+        let path = dir.join("proc_art_test_sampled_field.exr");
+        field.save_exr(&path).unwrap();
+        let loaded = SampledField::<2>::load_exr(&path, field.origin, field.size).unwrap();
+        assert_eq!(loaded.resolution, field.resolution);
+        let (a, b) = (loaded.lookup([2.2, 3.3]), field.lookup([2.2, 3.3]));
+        assert!((a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,350 @@
+//! Statistics of sampled noise, and remapping noise to use its whole output range.
+//!
+//! Noise rarely spans exactly [-1, 1]: single octaves of Perlin noise stay well
+//! inside it and fractals can overshoot.  [`NoiseStats`] samples a region of a
+//! noise function, and [`Normalized`] remaps the function so the sampled values
+//! cover [-1, 1], either linearly or with histogram equalization.
+
+use clap::ValueEnum;
+use noise::NoiseFn;
+
+/// Sorted samples of a noise function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseStats {
+    samples: Vec<f64>,
+}
+
+impl NoiseStats {
+    /// Sample `noise` at the centers of a `resolution` grid of cells covering
+    /// `[0, size[0]] x [0, size[1]]`.  Non-finite values are skipped.
+    pub fn sample<N>(noise: &N, size: [f64; 2], resolution: [usize; 2]) -> Self
+    where
+        N: NoiseFn<f64, 2> + ?Sized,
+    {
+        let [width, height] = resolution.map(|n| n.max(1));
+        let (dx, dy) = (size[0] / width as f64, size[1] / height as f64);
+        let samples = (0..height)
+            .flat_map(|j| (0..width).map(move |i| [(i as f64 + 0.5) * dx, (j as f64 + 0.5) * dy]))
+            .map(|point| noise.get(point));
+        Self::from_samples(samples)
+    }
+
+    pub fn from_samples(samples: impl IntoIterator<Item = f64>) -> Self {
+        let mut samples: Vec<f64> = samples.into_iter().filter(|v| v.is_finite()).collect();
+        samples.sort_by(f64::total_cmp);
+        NoiseStats { samples }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The smallest sample, or 0 without samples.
+    pub fn min(&self) -> f64 {
+        self.samples.first().copied().unwrap_or_default()
+    }
+
+    /// The largest sample, or 0 without samples.
+    pub fn max(&self) -> f64 {
+        self.samples.last().copied().unwrap_or_default()
+    }
+
+    /// The mean sample, or 0 without samples.
+    pub fn mean(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.;
+        }
+        self.samples.iter().sum::<f64>() / self.samples.len() as f64
+    }
+
+    /// The value below which a fraction `p` of the samples lie, interpolating between
+    /// samples.  `p` is clamped to [0, 1].
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.samples.is_empty() {
+            return 0.;
+        }
+        let i = p.clamp(0., 1.) * (self.samples.len() - 1) as f64;
+        let (lo, hi) = (
+            self.samples[i.floor() as usize],
+            self.samples[i.ceil() as usize],
+        );
+        lo + (hi - lo) * i.fract()
+    }
+
+    /// Counts of samples in `bins` equal-width bins from the min to the max.
+    pub fn histogram(&self, bins: usize) -> Vec<usize> {
+        let mut counts = vec![0; bins];
+        if bins == 0 {
+            return counts;
+        }
+        let (min, range) = (self.min(), self.max() - self.min());
+        for v in &self.samples {
+            let bin = if range > 0. {
+                ((v - min) / range * bins as f64) as usize
+            } else {
+                0
+            };
+            counts[bin.min(bins - 1)] += 1;
+        }
+        counts
+    }
+}
+
+/// How [`Normalized`] remaps noise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Normalization {
+    /// Stretch the sampled min and max to -1 and 1.
+    MinMax,
+    /// Stretch percentiles to -1 and 1, clamping the outliers beyond them.
+    Percentile,
+    /// Remap by the sampled distribution, so every output value is equally common.
+    Equalize,
+}
+
+/// Noise remapped piecewise linearly, clamped to the ends of the mapping.
+///
+/// Build it from [`NoiseStats`] of the same source, so the sampled values map
+/// onto [-1, 1].
+pub struct Normalized<Source> {
+    pub source: Source,
+    /// input and output values, sorted by input with no repeats
+    knots: Vec<[f64; 2]>,
+}
+
+impl<Source> Normalized<Source> {
+    /// Number of quantiles used by [`Normalized::equalize`].
+    pub const EQUALIZE_STEPS: usize = 256;
+
+    /// Map the min and max of `stats` onto -1 and 1.
+    pub fn min_max(source: Source, stats: &NoiseStats) -> Self {
+        Self::new(source, vec![[stats.min(), -1.], [stats.max(), 1.]])
+    }
+
+    /// Map the `clip` and `1 - clip` percentiles of `stats` onto -1 and 1.
+    ///
+    /// Panics unless `clip` is in [0, 0.5), which keeps the percentiles in order.
+    pub fn percentile(source: Source, stats: &NoiseStats, clip: f64) -> Self {
+        assert!((0. ..0.5).contains(&clip), "clip must be in [0, 0.5)");
+        let knots = vec![
+            [stats.percentile(clip), -1.],
+            [stats.percentile(1. - clip), 1.],
+        ];
+        Self::new(source, knots)
+    }
+
+    /// Map each sample onto its rank among the `stats` samples, scaled to [-1, 1].
+    pub fn equalize(source: Source, stats: &NoiseStats) -> Self {
+        let steps = Self::EQUALIZE_STEPS;
+        let knots = (0..=steps)
+            .map(|i| {
+                let p = i as f64 / steps as f64;
+                [stats.percentile(p), p * 2. - 1.]
+            })
+            .collect();
+        Self::new(source, knots)
+    }
+
+    pub fn with(
+        source: Source,
+        stats: &NoiseStats,
+        normalization: Normalization,
+        clip: f64,
+    ) -> Self {
+        match normalization {
+            Normalization::MinMax => Self::min_max(source, stats),
+            Normalization::Percentile => Self::percentile(source, stats, clip),
+            Normalization::Equalize => Self::equalize(source, stats),
+        }
+    }
+
+    /// Knots with equal inputs are merged into one at their mean output, so flat
+    /// regions of noise land in the middle of their range.
+    fn new(source: Source, knots: Vec<[f64; 2]>) -> Self {
+        let mut merged: Vec<([f64; 2], usize)> = vec![];
+        for [x, y] in knots {
+            match merged.last_mut() {
+                Some(([last_x, sum], n)) if *last_x == x => {
+                    *sum += y;
+                    *n += 1;
+                }
+                _ => merged.push(([x, y], 1)),
+            }
+        }
+        let knots = merged
+            .into_iter()
+            .map(|([x, sum], n)| [x, sum / n as f64])
+            .collect();
+        Normalized { source, knots }
+    }
+}
+
+impl<Source: NoiseFn<f64, 2>> NoiseFn<f64, 2> for Normalized<Source> {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        let v = self.source.get(point);
+        let i = self.knots.partition_point(|[x, _]| *x < v);
+        if i == 0 {
+            return self.knots.first().map_or(v, |[_, y]| *y);
+        }
+        if i == self.knots.len() {
+            return self.knots[i - 1][1];
+        }
+        let ([x0, y0], [x1, y1]) = (self.knots[i - 1], self.knots[i]);
+        y0 + (y1 - y0) * (v - x0) / (x1 - x0)
+    }
+}
+
+// Normalization options to flatten into a binary's CLI.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct NormalizeArgs {
+    /// remap noise values from samples of the image, so the whole palette is used
+    #[arg(long, value_enum)]
+    pub normalize: Option<Normalization>,
+
+    /// fraction of samples clipped at each end by percentile normalization, below 0.5
+    #[arg(long, default_value_t = 0.02, value_parser = parse_clip)]
+    pub normalize_clip: f64,
+}
+
+fn parse_clip(s: &str) -> Result<f64, String> {
+    let clip: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if (0. ..0.5).contains(&clip) {
+        Ok(clip)
+    } else {
+        Err(String::from("must be at least 0 and below 0.5"))
+    }
+}
+
+impl NormalizeArgs {
+    /// Samples taken along each axis of the image.
+    pub const RESOLUTION: usize = 256;
+
+    /// Normalize `noise` by samples over `[0, size[0]] x [0, size[1]]`, if `--normalize`
+    /// was given.
+    pub fn apply(
+        &self,
+        noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+        size: [f64; 2],
+    ) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        let stats = self.sample(std::slice::from_ref(&noise), size);
+        self.apply_stats(noise, stats.as_ref())
+    }
+
+    /// Samples of every frame of an animation pooled together, so all frames are
+    /// normalized the same way instead of flickering.  About as many samples are taken
+    /// as for a single image.  `None` without `--normalize`.
+    pub fn sample(
+        &self,
+        frames: &[Box<dyn NoiseFn<f64, 2> + Send + Sync>],
+        size: [f64; 2],
+    ) -> Option<NoiseStats> {
+        self.normalize?;
+        let per_frame = Self::RESOLUTION as f64 / (frames.len().max(1) as f64).sqrt();
+        let resolution = [per_frame.ceil() as usize; 2];
+        let samples = frames
+            .iter()
+            .flat_map(|noise| NoiseStats::sample(noise.as_ref(), size, resolution).samples);
+        Some(NoiseStats::from_samples(samples))
+    }
+
+    /// Normalize `noise` by `stats` from [`NormalizeArgs::sample`], if `--normalize` was
+    /// given.
+    pub fn apply_stats(
+        &self,
+        noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+        stats: Option<&NoiseStats>,
+    ) -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+        match (self.normalize, stats) {
+            (Some(normalization), Some(stats)) => Box::new(Normalized::with(
+                noise,
+                stats,
+                normalization,
+                self.normalize_clip,
+            )),
+            _ => noise,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::{Fbm, Perlin, ScaleBias};
+
+    use super::*;
+    use crate::noise::test_util::X;
+
+    #[test]
+    fn test_noise_stats() {
+        // x at cell centers 0.5, 1.5, ..., 9.5, on each of 3 rows
+        let stats = NoiseStats::sample(&X, [10., 1.], [10, 3]);
+        assert_eq!(stats.len(), 30);
+        assert_eq!((stats.min(), stats.max(), stats.mean()), (0.5, 9.5, 5.));
+        assert_eq!(stats.percentile(0.), 0.5);
+        assert_eq!(stats.percentile(0.5), 5.);
+        assert_eq!(stats.percentile(2.), 9.5);
+        assert_eq!(stats.histogram(5), vec![6; 5]);
+        assert_eq!(
+            NoiseStats::from_samples([1., f64::NAN, 1.]).histogram(2),
+            [2, 0]
+        );
+    }
+
+    #[test]
+    fn test_normalized_ranges() {
+        let size = [8., 8.];
+        let stats = NoiseStats::sample(&Perlin::new(1), size, [64, 64]);
+        let min_max = Normalized::min_max(Perlin::new(1), &stats);
+        let normalized = NoiseStats::sample(&min_max, size, [64, 64]);
+        assert!((normalized.min() + 1.).abs() < 1e-9);
+        assert!((normalized.max() - 1.).abs() < 1e-9);
+
+        let clipped = Normalized::percentile(Perlin::new(1), &stats, 0.1);
+        let clipped = NoiseStats::sample(&clipped, size, [64, 64]);
+        assert!((clipped.percentile(0.05) + 1.).abs() < 1e-9);
+        assert!((clipped.percentile(0.95) - 1.).abs() < 1e-9);
+
+        // equalized fractal noise fills each bin about equally
+        let fbm = Fbm::<Perlin>::new(2);
+        let stats = NoiseStats::sample(&fbm, size, [64, 64]);
+        let equalized = Normalized::equalize(fbm, &stats);
+        let counts = NoiseStats::sample(&equalized, size, [64, 64]).histogram(8);
+        for count in counts {
+            assert!((count as i32 - 512).abs() < 16, "{}", count);
+        }
+
+        // constant noise lands in the middle
+        let flat = NoiseStats::from_samples([0.3; 10]);
+        assert_eq!(Normalized::equalize(X, &flat).get([0.3, 0.]), 0.);
+    }
+
+    #[test]
+    fn test_normalize_args() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            normalize: NormalizeArgs,
+        }
+
+        assert!(Cli::try_parse_from(["test", "--normalize-clip", "0.5"]).is_err());
+        assert!(Cli::try_parse_from(["test", "--normalize-clip", "-0.1"]).is_err());
+        let args = Cli::parse_from(["test", "--normalize", "min-max"]).normalize;
+
+        // frames spanning [0, 1] and [1, 2] are normalized together, to [-1, 0] and [0, 1]
+        let size = [1., 1.];
+        let frame = |i: usize| -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+            Box::new(ScaleBias::new(X).set_bias(i as f64))
+        };
+        let stats = args.sample(&[frame(0), frame(1)], size).unwrap();
+        let [a, b] = [0, 1].map(|i| {
+            let noise = args.apply_stats(frame(i), Some(&stats));
+            NoiseStats::sample(noise.as_ref(), size, [64, 1])
+        });
+        assert!(a.min() < -0.95 && a.max() < 0.05);
+        assert!(b.min() > -0.05 && b.max() > 0.95);
+    }
+}
@@ -0,0 +1,58 @@
+//! Seamlessly tiling noise, sampled from 4D noise on a torus.
+
+use std::f64::consts::TAU;
+
+use noise::NoiseFn;
+
+/// 2D noise that repeats every `period` units along x and y.
+///
+/// Each axis is wrapped onto a circle whose circumference is its period, and
+/// the two circles form a torus in 4D noise space, so opposite edges meet
+/// with no seam.  Features keep about the size they have in the plane, but
+/// the curvature of the torus stretches them slightly.
+pub struct Tileable<Source> {
+    pub source: Source,
+    pub period: [f64; 2],
+}
+
+impl<Source> Tileable<Source> {
+    /// Panics unless both periods are positive and finite.
+    pub fn new(source: Source, period: [f64; 2]) -> Self {
+        assert!(
+            period.iter().all(|p| p.is_finite() && *p > 0.),
+            "period must be positive"
+        );
+        Tileable { source, period }
+    }
+}
+
+impl<Source: NoiseFn<f64, 4>> NoiseFn<f64, 2> for Tileable<Source> {
+    fn get(&self, [x, y]: [f64; 2]) -> f64 {
+        let [px, py] = self.period;
+        let (sin_x, cos_x) = (x / px * TAU).sin_cos();
+        let (sin_y, cos_y) = (y / py * TAU).sin_cos();
+        let (rx, ry) = (px / TAU, py / TAU);
+        self.source
+            .get([rx * cos_x, rx * sin_x, ry * cos_y, ry * sin_y])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::PerlinSurflet;
+
+    use super::*;
+
+    #[test]
+    fn test_tileable_repeats() {
+        let noise = Tileable::new(PerlinSurflet::new(4), [5., 3.]);
+        for (x, y) in [(0.3, 0.7), (1.9, 2.4), (4.5, 0.)] {
+            let v = noise.get([x, y]);
+            assert!((noise.get([x + 5., y]) - v).abs() < 1e-9);
+            assert!((noise.get([x, y - 3.]) - v).abs() < 1e-9);
+            assert!((noise.get([x + 10., y + 6.]) - v).abs() < 1e-9);
+        }
+        // but doesn't repeat within a period
+        assert_ne!(noise.get([0.3, 0.7]), noise.get([2.8, 0.7]));
+    }
+}
@@ -0,0 +1,113 @@
+//! Domain warping: sampling noise at points displaced by other noise.
+
+use noise::NoiseFn;
+
+use crate::flow::FlowField;
+
+/// A 2D vector field made of one noise function per axis.
+pub struct WarpField {
+    pub x: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+    pub y: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+}
+
+/// Noise sampled at warped points, `source(p + strength * field(p))`.
+///
+/// Iterations nest the warp, each sampling the next field at the previously
+/// warped point: `q1 = p + k * f1(p)`, `q2 = p + k * f2(q1)`, and so on, with
+/// the source sampled at the last.  Fields are cycled through when there are
+/// more iterations than fields.  Without fields the source is sampled as is.
+pub struct DomainWarp<Source> {
+    pub source: Source,
+    pub fields: Vec<WarpField>,
+    pub strength: f64,
+    pub iterations: usize,
+}
+
+impl<Source> DomainWarp<Source> {
+    pub const DEFAULT_STRENGTH: f64 = 1.;
+    pub const DEFAULT_ITERATIONS: usize = 1;
+
+    pub fn new(source: Source) -> Self {
+        DomainWarp {
+            source,
+            fields: vec![],
+            strength: Self::DEFAULT_STRENGTH,
+            iterations: Self::DEFAULT_ITERATIONS,
+        }
+    }
+
+    pub fn add_field(
+        mut self,
+        x: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+        y: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+    ) -> Self {
+        self.fields.push(WarpField { x, y });
+        self
+    }
+
+    pub fn set_strength(self, strength: f64) -> Self {
+        DomainWarp { strength, ..self }
+    }
+
+    pub fn set_iterations(self, iterations: usize) -> Self {
+        DomainWarp { iterations, ..self }
+    }
+}
+
+impl<Source: NoiseFn<f64, 2>> NoiseFn<f64, 2> for DomainWarp<Source> {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        let [x, y] = point;
+        let warped = self
+            .fields
+            .iter()
+            .cycle()
+            .take(self.iterations)
+            .fold(point, |q, field| {
+                let d = field.sample(q.into());
+                [x + self.strength * d.x, y + self.strength * d.y]
+            });
+        self.source.get(warped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::{Constant, Perlin};
+
+    use super::*;
+    use crate::noise::test_util::X;
+
+    #[test]
+    fn test_domain_warp_offsets_points() {
+        let source = Perlin::new(3);
+        let point = [0.3, 0.7];
+        let unwarped = DomainWarp::new(Perlin::new(3));
+        assert_eq!(unwarped.get(point), source.get(point));
+
+        let shifted = DomainWarp::new(Perlin::new(3))
+            .add_field(Box::new(Constant::new(1.)), Box::new(Constant::new(-0.5)))
+            .set_strength(2.);
+        assert_eq!(shifted.get(point), source.get([2.3, -0.3]));
+
+        // a constant field lands on the same point however many times it is applied
+        let iterated = shifted.set_iterations(4);
+        assert_eq!(iterated.get(point), source.get([2.3, -0.3]));
+    }
+
+    #[test]
+    fn test_domain_warp_nests_fields() {
+        // q1 = p + (x, 0) = (2x, y), q2 = p + (2x, 0) = (3x, y)
+        let warp = DomainWarp::new(X)
+            .add_field(Box::new(X), Box::new(Constant::new(0.)))
+            .set_iterations(2);
+        assert_eq!(warp.get([2., 5.]), 6.);
+
+        // fields are cycled: q1 = p + (1, 0), q2 = p + (x1, 0), q3 = p + (1, 0)
+        let cycled = DomainWarp::new(X)
+            .add_field(Box::new(Constant::new(1.)), Box::new(Constant::new(0.)))
+            .add_field(Box::new(X), Box::new(Constant::new(0.)))
+            .set_iterations(3);
+        assert_eq!(cycled.get([2., 5.]), 3.);
+        assert_eq!(cycled.set_iterations(2).get([2., 5.]), 5.);
+    }
+}
@@ -0,0 +1,235 @@
+//! Extract a color palette from a reference image.
+//!
+//! Pixels are quantized in OKLab, so that clusters follow perceived color
+//! differences rather than raw sRGB distances.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use image::{ImageResult, RgbaImage};
+use palette::Oklab;
+use tiny_skia::Color;
+
+use crate::skia_colors::{color_to_oklab, oklab_to_color};
+
+/// Pixels with alpha below this are ignored.
+const MIN_ALPHA: u8 = 128;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum QuantizeMethod {
+    /// Recursively split the color box with the widest extent at its median.
+    MedianCut,
+    /// Lloyd's k-means, initialized from a median cut.
+    #[default]
+    KMeans,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractOptions {
+    pub n_colors: usize,
+    pub method: QuantizeMethod,
+    /// Images are subsampled to at most this many pixels before quantizing.
+    pub max_samples: usize,
+    /// Maximum number of k-means iterations.
+    pub max_iterations: usize,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            n_colors: 8,
+            method: QuantizeMethod::default(),
+            max_samples: 100_000,
+            max_iterations: 32,
+        }
+    }
+}
+
+/// Load an image and extract a palette from it.
+pub fn extract_palette_from_file(
+    path: impl AsRef<Path>,
+    opts: &ExtractOptions,
+) -> ImageResult<Vec<Color>> {
+    let img = image::open(path)?.to_rgba8();
+    Ok(extract_palette(&img, opts))
+}
+
+/// Quantize an image to at most `opts.n_colors` colors, ordered from dark to light.
+pub fn extract_palette(img: &RgbaImage, opts: &ExtractOptions) -> Vec<Color> {
+    let samples = sample_pixels(img, opts.max_samples);
+    if samples.is_empty() || opts.n_colors == 0 {
+        return vec![];
+    }
+
+    let mut centroids = median_cut(samples.clone(), opts.n_colors);
+    if opts.method == QuantizeMethod::KMeans {
+        centroids = k_means(&samples, centroids, opts.max_iterations);
+    }
+
+    centroids.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    let mut colors: Vec<_> = centroids
+        .into_iter()
+        .map(|[l, a, b]| oklab_to_color(Oklab::new(l, a, b)))
+        .collect();
+    // heavily skewed images can produce clusters that round to the same color
+    colors.dedup_by_key(|c| c.to_color_u8());
+    colors
+}
+
+type Lab = [f32; 3];
+
+fn sample_pixels(img: &RgbaImage, max_samples: usize) -> Vec<Lab> {
+    let n_pixels = img.width() as usize * img.height() as usize;
+    let stride = (n_pixels / max_samples.max(1)).max(1);
+    img.pixels()
+        .step_by(stride)
+        .filter(|p| p[3] >= MIN_ALPHA)
+        .map(|p| {
+            let lab = color_to_oklab(&Color::from_rgba8(p[0], p[1], p[2], 255));
+            [lab.l, lab.a, lab.b]
+        })
+        .collect()
+}
+
+fn add(a: Lab, b: &Lab) -> Lab {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn mean(points: &[Lab]) -> Lab {
+    let sum = points.iter().fold([0.; 3], add);
+    sum.map(|s| s / points.len() as f32)
+}
+
+fn distance_squared(a: &Lab, b: &Lab) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum()
+}
+
+/// Widest axis of a set of points and its extent.
+fn widest_axis(points: &[Lab]) -> (usize, f32) {
+    (0..3)
+        .map(|axis| {
+            let (lo, hi) = points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| {
+                (lo.min(p[axis]), hi.max(p[axis]))
+            });
+            (axis, hi - lo)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+}
+
+fn median_cut(points: Vec<Lab>, n_colors: usize) -> Vec<Lab> {
+    let mut boxes = vec![points];
+    while boxes.len() < n_colors {
+        // split the box with the largest extent, weighted by population
+        let Some((idx, axis)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (axis, extent) = widest_axis(b);
+                (i, axis, extent * (b.len() as f32).sqrt())
+            })
+            .filter(|(_, _, score)| *score > 0.)
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(i, axis, _)| (i, axis))
+        else {
+            break;
+        };
+
+        let mut split = boxes.swap_remove(idx);
+        split.sort_by(|a, b| a[axis].total_cmp(&b[axis]));
+        let upper = split.split_off(split.len() / 2);
+        boxes.push(split);
+        boxes.push(upper);
+    }
+    boxes.iter().map(|b| mean(b)).collect()
+}
+
+fn k_means(points: &[Lab], mut centroids: Vec<Lab>, max_iterations: usize) -> Vec<Lab> {
+    let mut assignments = vec![usize::MAX; points.len()];
+    for _ in 0..max_iterations {
+        let mut changed = false;
+        for (p, assigned) in points.iter().zip(assignments.iter_mut()) {
+            let nearest = (0..centroids.len())
+                .min_by(|&a, &b| {
+                    distance_squared(p, &centroids[a])
+                        .total_cmp(&distance_squared(p, &centroids[b]))
+                })
+                .unwrap();
+            if *assigned != nearest {
+                *assigned = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![([0.; 3], 0usize); centroids.len()];
+        for (p, &c) in points.iter().zip(assignments.iter()) {
+            sums[c].0 = add(sums[c].0, p);
+            sums[c].1 += 1;
+        }
+        for (centroid, (sum, count)) in centroids.iter_mut().zip(sums) {
+            // empty clusters keep their previous position
+            if count > 0 {
+                *centroid = sum.map(|s| s / count as f32);
+            }
+        }
+    }
+    centroids
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// Image split into horizontal bands of solid color.
+    fn banded_image(colors: &[[u8; 4]]) -> RgbaImage {
+        RgbaImage::from_fn(16, 4 * colors.len() as u32, |_, y| {
+            Rgba(colors[y as usize / 4])
+        })
+    }
+
+    #[test]
+    fn test_extract_palette() {
+        let img = banded_image(&[
+            [255, 255, 255, 255],
+            [200, 20, 20, 255],
+            [0, 255, 0, 0],
+            [0, 0, 0, 255],
+            [100, 180, 255, 255],
+        ]);
+        let expected = [
+            Color::from_rgba8(0, 0, 0, 255),
+            Color::from_rgba8(200, 20, 20, 255),
+            Color::from_rgba8(100, 180, 255, 255),
+            Color::from_rgba8(255, 255, 255, 255),
+        ];
+        for method in [QuantizeMethod::MedianCut, QuantizeMethod::KMeans] {
+            let opts = ExtractOptions {
+                n_colors: 4,
+                method,
+                ..Default::default()
+            };
+            let palette: Vec<_> = extract_palette(&img, &opts)
+                .iter()
+                .map(|c| c.to_color_u8())
+                .collect();
+            let expected: Vec<_> = expected.iter().map(|c| c.to_color_u8()).collect();
+            assert_eq!(palette, expected, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn test_extract_palette_fewer_distinct_colors() {
+        let img = banded_image(&[[10, 20, 30, 255]]);
+        let opts = ExtractOptions {
+            n_colors: 4,
+            ..Default::default()
+        };
+        assert_eq!(extract_palette(&img, &opts).len(), 1);
+    }
+}
@@ -0,0 +1,567 @@
+//! Point sets for placing seeds, e.g. where flow walks start.
+//!
+//! Uniform random points clump together and leave gaps.  Poisson-disk points
+//! keep a minimum distance apart, grids are regular and jittered grids are
+//! regular on average, and the Halton and Sobol sequences cover the plane
+//! evenly without looking regular.  Any of them can be weighted by a
+//! [`Density`], from a grayscale image or from noise, to put more points where
+//! it's bright.
+
+use std::f64::consts::{SQRT_2, TAU};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use image::{ImageBuffer, ImageResult, Luma};
+use na::Vector2;
+use noise::NoiseFn;
+use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
+
+/// Candidates tried around each Poisson-disk point before it stops spawning more.
+const POISSON_CANDIDATES: usize = 30;
+
+/// Weighted random points and sequences give up after this many rejections per point.
+const MAX_TRIES_PER_POINT: usize = 100;
+
+/// How points are spread over a rectangle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PointPattern {
+    /// Independent uniform random points.
+    #[default]
+    Random,
+    /// Random points at least the spacing apart, by Bridson's algorithm.
+    Poisson,
+    /// Centers of square grid cells.
+    Grid,
+    /// Grid points moved randomly within their cells.
+    Jittered,
+    /// The Halton sequence in bases 2 and 3, randomly shifted.
+    Halton,
+    /// The two dimensional Sobol sequence, randomly shifted.
+    Sobol,
+}
+
+/// How likely a point is to be kept at each position, from 0 to 1.
+pub trait Density {
+    fn density(&self, pos: Vector2<f64>) -> f64;
+}
+
+impl<D: Density + ?Sized> Density for Box<D> {
+    fn density(&self, pos: Vector2<f64>) -> f64 {
+        (**self).density(pos)
+    }
+}
+
+impl<D: Density + ?Sized> Density for &D {
+    fn density(&self, pos: Vector2<f64>) -> f64 {
+        (**self).density(pos)
+    }
+}
+
+/// The brightness of a grayscale image stretched over a rectangle.
+pub struct ImageDensity {
+    image: ImageBuffer<Luma<f32>, Vec<f32>>,
+    size: [f64; 2],
+}
+
+impl ImageDensity {
+    pub fn new(image: ImageBuffer<Luma<f32>, Vec<f32>>, size: [f64; 2]) -> Self {
+        ImageDensity { image, size }
+    }
+
+    /// Load an image, converting color images to grayscale.
+    pub fn open(path: impl AsRef<Path>, size: [f64; 2]) -> ImageResult<Self> {
+        Ok(Self::new(image::open(path)?.to_luma32f(), size))
+    }
+}
+
+impl Density for ImageDensity {
+    fn density(&self, pos: Vector2<f64>) -> f64 {
+        let (width, height) = self.image.dimensions();
+        if width == 0 || height == 0 {
+            return 0.;
+        }
+        let pixel = |v: f64, size: f64, n: u32| ((v / size * n as f64) as u32).min(n - 1);
+        let x = pixel(pos.x, self.size[0], width);
+        let y = pixel(pos.y, self.size[1], height);
+        (self.image.get_pixel(x, y).0[0] as f64).clamp(0., 1.)
+    }
+}
+
+/// Noise mapped from -1..1 to a density of 0..1.
+pub struct NoiseDensity<Source> {
+    pub source: Source,
+}
+
+impl<Source> NoiseDensity<Source> {
+    pub fn new(source: Source) -> Self {
+        NoiseDensity { source }
+    }
+}
+
+impl<Source: NoiseFn<f64, 2>> Density for NoiseDensity<Source> {
+    fn density(&self, pos: Vector2<f64>) -> f64 {
+        ((self.source.get(pos.into()) + 1.) / 2.).clamp(0., 1.)
+    }
+}
+
+/// A density with light and dark swapped.
+pub struct Invert<Source>(pub Source);
+
+impl<Source: Density> Density for Invert<Source> {
+    fn density(&self, pos: Vector2<f64>) -> f64 {
+        1. - self.0.density(pos)
+    }
+}
+
+/// `n` uniform random points in `[0, size[0]) x [0, size[1])`.
+pub fn random(size: [f64; 2], n: usize, rng: &mut impl Rng) -> Vec<Vector2<f64>> {
+    let (x, y) = (Uniform::new(0., size[0]), Uniform::new(0., size[1]));
+    (0..n)
+        .map(|_| Vector2::new(x.sample(rng), y.sample(rng)))
+        .collect()
+}
+
+/// Random points in `[0, size[0]) x [0, size[1])`, no two closer than `radius`, packed
+/// until no more fit.
+pub fn poisson_disk(size: [f64; 2], radius: f64, rng: &mut impl Rng) -> Vec<Vector2<f64>> {
+    // each cell is small enough to hold at most one point
+    let cell = radius / SQRT_2;
+    let [nx, ny] = size.map(|n| (n / cell).ceil() as usize);
+    if nx == 0 || ny == 0 || radius <= 0. {
+        return vec![];
+    }
+    let cell_of = |p: Vector2<f64>| {
+        (
+            ((p.x / cell) as usize).min(nx - 1),
+            ((p.y / cell) as usize).min(ny - 1),
+        )
+    };
+    let in_bounds =
+        |p: Vector2<f64>| (0. ..size[0]).contains(&p.x) && (0. ..size[1]).contains(&p.y);
+
+    let mut grid: Vec<Option<usize>> = vec![None; nx * ny];
+    let mut points = random(size, 1, rng);
+    let (x, y) = cell_of(points[0]);
+    grid[y * nx + x] = Some(0);
+    let mut active = vec![0];
+    while !active.is_empty() {
+        let i = rng.gen_range(0..active.len());
+        let center = points[active[i]];
+        let mut found = None;
+        for _ in 0..POISSON_CANDIDATES {
+            // uniform over the annulus between one and two radii
+            let angle = rng.gen_range(0. ..TAU);
+            let distance = rng.gen_range(radius * radius..4. * radius * radius).sqrt();
+            let p = center + Vector2::new(angle.cos(), angle.sin()) * distance;
+            if !in_bounds(p) {
+                continue;
+            }
+            let (cx, cy) = cell_of(p);
+            let far = (cy.saturating_sub(2)..(cy + 3).min(ny))
+                .flat_map(|y| (cx.saturating_sub(2)..(cx + 3).min(nx)).map(move |x| (x, y)))
+                .filter_map(|(x, y)| grid[y * nx + x])
+                .all(|j| (points[j] - p).norm() >= radius);
+            if far {
+                found = Some((p, cx, cy));
+                break;
+            }
+        }
+        match found {
+            Some((p, x, y)) => {
+                grid[y * nx + x] = Some(points.len());
+                active.push(points.len());
+                points.push(p);
+            }
+            None => {
+                active.swap_remove(i);
+            }
+        }
+    }
+    points
+}
+
+/// Centers of the square cells `spacing` wide that fit in `[0, size[0]) x [0, size[1])`,
+/// with the grid centered.  There's always at least one cell across each axis.
+pub fn grid(size: [f64; 2], spacing: f64) -> Vec<Vector2<f64>> {
+    let [nx, ny] = size.map(|n| (n / spacing).floor().max(1.) as usize);
+    let [x0, y0] = [(size[0], nx), (size[1], ny)]
+        .map(|(length, n)| (length - n as f64 * spacing) / 2. + spacing / 2.);
+    (0..ny)
+        .flat_map(|j| (0..nx).map(move |i| (i, j)))
+        .map(|(i, j)| Vector2::new(x0 + i as f64 * spacing, y0 + j as f64 * spacing))
+        .collect()
+}
+
+/// [`grid`] points, each moved up to `jitter * spacing / 2` along each axis.  A jitter of
+/// 1 moves points anywhere in their cells.
+pub fn jittered_grid(
+    size: [f64; 2],
+    spacing: f64,
+    jitter: f64,
+    rng: &mut impl Rng,
+) -> Vec<Vector2<f64>> {
+    let offset = Uniform::new_inclusive(-0.5, 0.5);
+    let clamp = |v: f64, length: f64| v.clamp(0., (length - f64::EPSILON * length).max(0.));
+    grid(size, spacing)
+        .into_iter()
+        .map(|p| {
+            let dx = offset.sample(rng) * jitter * spacing;
+            let dy = offset.sample(rng) * jitter * spacing;
+            Vector2::new(clamp(p.x + dx, size[0]), clamp(p.y + dy, size[1]))
+        })
+        .collect()
+}
+
+/// The digits of `index` in `base` mirrored about the radix point, e.g. 6 = 110 in base
+/// 2 gives 0.011 = 0.375.
+pub fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let (mut result, mut digit_value) = (0., 1.);
+    while index > 0 {
+        digit_value /= base as f64;
+        result += digit_value * (index % base) as f64;
+        index /= base;
+    }
+    result
+}
+
+/// Point `index` of the Halton sequence in the unit square, in bases 2 and 3.
+pub fn halton(index: u64) -> [f64; 2] {
+    [radical_inverse(index, 2), radical_inverse(index, 3)]
+}
+
+/// Point `index` of the two dimensional Sobol sequence in the unit square.
+pub fn sobol(index: u32) -> [f64; 2] {
+    let (mut x, mut y) = (0u32, 0u32);
+    // direction numbers of the second dimension, from the polynomial x + 1
+    let mut direction = 1u32 << 31;
+    for bit in 0..32 {
+        if index >> bit & 1 == 1 {
+            x ^= 1 << (31 - bit);
+            y ^= direction;
+        }
+        direction ^= direction >> 1;
+    }
+    let unit = |v: u32| v as f64 / (1u64 << 32) as f64;
+    [unit(x), unit(y)]
+}
+
+/// Keep each point with the probability given by `density`.
+pub fn thin<D>(points: Vec<Vector2<f64>>, density: &D, rng: &mut impl Rng) -> Vec<Vector2<f64>>
+where
+    D: Density + ?Sized,
+{
+    points
+        .into_iter()
+        .filter(|p| rng.gen::<f64>() < density.density(*p))
+        .collect()
+}
+
+/// A point pattern and its parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Seeding {
+    pub pattern: PointPattern,
+    /// Distance between points of spaced patterns, or the spacing of the requested
+    /// number of points on a grid if `None`.
+    pub spacing: Option<f64>,
+    /// Distance jittered grid points move, as a fraction of the spacing.
+    pub jitter: f64,
+}
+
+impl Seeding {
+    pub fn new(pattern: PointPattern) -> Self {
+        Seeding {
+            pattern,
+            spacing: None,
+            jitter: 1.,
+        }
+    }
+
+    /// Panics unless `spacing` is positive and finite.
+    pub fn set_spacing(self, spacing: f64) -> Self {
+        assert!(
+            spacing.is_finite() && spacing > 0.,
+            "spacing must be positive"
+        );
+        Seeding {
+            spacing: Some(spacing),
+            ..self
+        }
+    }
+
+    pub fn set_jitter(self, jitter: f64) -> Self {
+        Seeding { jitter, ..self }
+    }
+
+    /// Points in `[0, size[0]) x [0, size[1])`, kept in proportion to `density` if given.
+    ///
+    /// Random points and sequences give `n` points, drawing more in place of those the
+    /// density rejects, up to a limit.  Poisson-disk and grid points fill the rectangle
+    /// at their spacing and are thinned by the density.
+    pub fn points<R: Rng>(
+        &self,
+        size: [f64; 2],
+        n: usize,
+        density: Option<&dyn Density>,
+        rng: &mut R,
+    ) -> Vec<Vector2<f64>> {
+        if n == 0 {
+            return vec![];
+        }
+        let spacing = self
+            .spacing
+            .unwrap_or_else(|| (size[0] * size[1] / n as f64).sqrt());
+        let spaced = match self.pattern {
+            PointPattern::Poisson => Some(poisson_disk(size, spacing, rng)),
+            PointPattern::Grid => Some(grid(size, spacing)),
+            PointPattern::Jittered => Some(jittered_grid(size, spacing, self.jitter, rng)),
+            PointPattern::Random | PointPattern::Halton | PointPattern::Sobol => None,
+        };
+        if let Some(points) = spaced {
+            return match density {
+                Some(density) => thin(points, density, rng),
+                None => points,
+            };
+        }
+
+        let (x, y) = (Uniform::new(0., size[0]), Uniform::new(0., size[1]));
+        // a random toroidal shift keeps sequences evenly spread, but differing by seed
+        let shift = match self.pattern {
+            PointPattern::Random => [0., 0.],
+            _ => [rng.gen(), rng.gen()],
+        };
+        let mut index = 0;
+        let mut next = |rng: &mut R| {
+            index += 1;
+            let unit = match self.pattern {
+                PointPattern::Halton => halton(index),
+                PointPattern::Sobol => sobol(index as u32),
+                _ => return Vector2::new(x.sample(rng), y.sample(rng)),
+            };
+            let [u, v] = [0, 1].map(|i| (unit[i] + shift[i]).fract());
+            Vector2::new(u * size[0], v * size[1])
+        };
+        let mut points = Vec::with_capacity(n);
+        for _ in 0..n * MAX_TRIES_PER_POINT {
+            if points.len() == n {
+                break;
+            }
+            let p = next(rng);
+            if density.map_or(true, |d| rng.gen::<f64>() < d.density(p)) {
+                points.push(p);
+            }
+        }
+        points
+    }
+}
+
+/// Where to take a seed density from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DensitySpec {
+    /// A noise function, built by the binary from its noise options.
+    Noise,
+    /// A grayscale image stretched over the output.
+    Image(PathBuf),
+}
+
+impl FromStr for DensitySpec {
+    type Err = String;
+
+    /// `noise`, or a path to an image.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "noise" {
+            return Ok(Self::Noise);
+        }
+        let path = PathBuf::from(s);
+        if !path.exists() {
+            return Err(format!(
+                "'{}' is neither \"noise\" nor an existing image",
+                s
+            ));
+        }
+        Ok(Self::Image(path))
+    }
+}
+
+// Seed placement options to flatten into a binary's CLI.
+#[derive(clap::Args, Debug, Clone)]
+pub struct SeedingArgs {
+    /// how walk start points are placed: at random, at least --seed-spacing apart
+    /// (poisson), on a grid, on a jittered grid, or along the halton or sobol sequences,
+    /// which spread evenly without a visible pattern
+    #[arg(long, value_enum, default_value_t = PointPattern::Random)]
+    pub seeding: PointPattern,
+
+    /// distance between poisson, grid and jittered seeds, in pixels [default: the grid
+    /// spacing of the number of walks]
+    #[arg(long, value_parser = parse_spacing)]
+    pub seed_spacing: Option<f64>,
+
+    /// how far jittered seeds move within their grid cells, from 0 to 1
+    #[arg(long, default_value_t = 1.)]
+    pub seed_jitter: f64,
+
+    /// place more seeds where this grayscale image, stretched over the output, is
+    /// bright, or where noise is high with "noise"
+    #[arg(long)]
+    pub seed_density: Option<DensitySpec>,
+
+    /// place more seeds where --seed-density is dark instead
+    #[arg(long, requires = "seed_density")]
+    pub seed_density_invert: bool,
+}
+
+fn parse_spacing(s: &str) -> Result<f64, String> {
+    let spacing: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if spacing.is_finite() && spacing > 0. {
+        Ok(spacing)
+    } else {
+        Err(String::from("must be a positive number"))
+    }
+}
+
+impl SeedingArgs {
+    pub fn seeding(&self) -> Seeding {
+        let seeding = Seeding::new(self.seeding).set_jitter(self.seed_jitter);
+        match self.seed_spacing {
+            Some(spacing) => seeding.set_spacing(spacing),
+            None => seeding,
+        }
+    }
+
+    /// The density from `--seed-density` over an image of `size` pixels, if given.
+    /// `noise` builds the noise for `--seed-density noise`, over pixel coordinates.
+    pub fn density<N>(
+        &self,
+        size: [f64; 2],
+        noise: impl FnOnce() -> N,
+    ) -> ImageResult<Option<Box<dyn Density + Send + Sync>>>
+    where
+        N: NoiseFn<f64, 2> + Send + Sync + 'static,
+    {
+        let density: Box<dyn Density + Send + Sync> = match &self.seed_density {
+            Some(DensitySpec::Noise) => Box::new(NoiseDensity::new(noise())),
+            Some(DensitySpec::Image(path)) => Box::new(ImageDensity::open(path, size)?),
+            None => return Ok(None),
+        };
+        Ok(Some(match self.seed_density_invert {
+            true => Box::new(Invert(density)),
+            false => density,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    /// Points left of the middle are always kept and the rest never are.
+    struct LeftHalf;
+
+    impl Density for LeftHalf {
+        fn density(&self, pos: Vector2<f64>) -> f64 {
+            if pos.x < 50. {
+                1.
+            } else {
+                0.
+            }
+        }
+    }
+
+    fn in_bounds(points: &[Vector2<f64>], size: [f64; 2]) -> bool {
+        points
+            .iter()
+            .all(|p| (0. ..size[0]).contains(&p.x) && (0. ..size[1]).contains(&p.y))
+    }
+
+    #[test]
+    fn test_poisson_disk() {
+        let rng = &mut ChaCha8Rng::seed_from_u64(1);
+        let size = [100., 60.];
+        let points = poisson_disk(size, 5., rng);
+        assert!(in_bounds(&points, size));
+        for (i, p) in points.iter().enumerate() {
+            assert!(points[i + 1..].iter().all(|q| (p - q).norm() >= 5.));
+        }
+        // maximal packings hold well over one point per 2r x 2r square
+        assert!(points.len() > 120, "{}", points.len());
+    }
+
+    #[test]
+    fn test_sequences_and_grids() {
+        assert_eq!(radical_inverse(6, 2), 0.375);
+        assert_eq!(halton(1), [0.5, 1. / 3.]);
+        let first: Vec<_> = (1..4).map(sobol).collect();
+        assert_eq!(first, [[0.5, 0.5], [0.25, 0.75], [0.75, 0.25]]);
+        // every point of a 2^k run of sobol points is alone in its column and row
+        let mut columns: Vec<_> = (0..16).map(|i| (sobol(i)[0] * 16.) as usize).collect();
+        let mut rows: Vec<_> = (0..16).map(|i| (sobol(i)[1] * 16.) as usize).collect();
+        columns.sort();
+        rows.sort();
+        assert_eq!(columns, (0..16).collect::<Vec<_>>());
+        assert_eq!(rows, (0..16).collect::<Vec<_>>());
+
+        let points = grid([100., 45.], 10.);
+        assert_eq!(points.len(), 40);
+        assert_eq!(points[0], Vector2::new(5., 7.5));
+        let rng = &mut ChaCha8Rng::seed_from_u64(2);
+        let jittered = jittered_grid([100., 45.], 10., 1., rng);
+        assert!(in_bounds(&jittered, [100., 45.]));
+        for (p, q) in points.iter().zip(&jittered) {
+            assert!((p - q).amax() <= 5.);
+        }
+    }
+
+    #[test]
+    fn test_seeding_density() {
+        let rng = &mut ChaCha8Rng::seed_from_u64(3);
+        let size = [100., 100.];
+        for pattern in PointPattern::value_variants() {
+            let seeding = Seeding::new(*pattern);
+            let points = seeding.points(size, 200, Some(&LeftHalf), rng);
+            assert!(in_bounds(&points, size), "{:?}", pattern);
+            assert!(points.iter().all(|p| p.x < 50.), "{:?}", pattern);
+            assert!(points.len() > 50, "{:?}", pattern);
+            let unweighted = seeding.points(size, 200, None, rng);
+            match pattern {
+                PointPattern::Random | PointPattern::Halton | PointPattern::Sobol => {
+                    assert_eq!(points.len(), 200);
+                    assert_eq!(unweighted.len(), 200);
+                }
+                _ => assert!(points.len() < unweighted.len()),
+            }
+        }
+        // nothing is accepted, but sampling still ends
+        let none = Invert(LeftHalf);
+        let points = Seeding::new(PointPattern::Random).points([40., 40.], 10, Some(&none), rng);
+        assert!(points.is_empty());
+    }
+
+    #[test]
+    fn test_seeding_args() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            seeding: SeedingArgs,
+        }
+
+        for bad in ["0", "-1", "NaN", "inf"] {
+            assert!(Cli::try_parse_from(["test", "--seed-spacing", bad]).is_err());
+        }
+        let args = Cli::parse_from(["test", "--seeding", "grid", "--seed-spacing", "5"]);
+        let seeding = args.seeding.seeding();
+        assert_eq!(seeding.spacing, Some(5.));
+        assert_eq!(
+            seeding
+                .points([20., 10.], 1, None, &mut ChaCha8Rng::seed_from_u64(1))
+                .len(),
+            8
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Fill pixmaps pixel by pixel, in parallel.
+//!
+//! Rows are split into chunks which rayon spreads over all cores, with a
+//! progress bar that counts chunks as they finish.
+
+use indicatif::ParallelProgressIterator;
+use noise::NoiseFn;
+use rayon::prelude::*;
+use tiny_skia::{Pixmap, PremultipliedColorU8};
+
+/// Rows of pixels filled by each parallel task.
+pub const ROWS_PER_CHUNK: usize = 16;
+
+/// Set each pixel of `pixmap` to `shade(x, y)`.
+pub fn par_fill<F>(pixmap: &mut Pixmap, shade: F)
+where
+    F: Fn(u32, u32) -> PremultipliedColorU8 + Sync,
+{
+    let width = pixmap.width() as usize;
+    if width == 0 {
+        return;
+    }
+    pixmap
+        .pixels_mut()
+        .par_chunks_mut(width * ROWS_PER_CHUNK)
+        .progress()
+        .enumerate()
+        .for_each(|(chunk, pixels)| {
+            for (row, pixels) in pixels.chunks_mut(width).enumerate() {
+                let y = (chunk * ROWS_PER_CHUNK + row) as u32;
+                for (x, pixel) in pixels.iter_mut().enumerate() {
+                    *pixel = shade(x as u32, y);
+                }
+            }
+        });
+}
+
+/// A new pixmap with each pixel colored by `color` from the noise at its coordinates.
+pub fn par_paint_noise<N, F>(noise: &N, width: u32, height: u32, color: F) -> Pixmap
+where
+    N: NoiseFn<f64, 2> + Sync + ?Sized,
+    F: Fn(f64) -> PremultipliedColorU8 + Sync,
+{
+    let mut pixmap = Pixmap::new(width, height).unwrap();
+    par_fill(&mut pixmap, |x, y| color(noise.get([x as f64, y as f64])));
+    pixmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_fill_matches_coordinates() {
+        // a height that doesn't divide into whole chunks
+        let (width, height) = (7, ROWS_PER_CHUNK as u32 * 2 + 3);
+        let mut pixmap = Pixmap::new(width, height).unwrap();
+        par_fill(&mut pixmap, |x, y| {
+            PremultipliedColorU8::from_rgba(x as u8, y as u8, 0, 255).unwrap()
+        });
+        for (i, pixel) in pixmap.pixels().iter().enumerate() {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            assert_eq!((pixel.red(), pixel.green()), (x as u8, y as u8));
+        }
+    }
+}
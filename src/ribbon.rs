@@ -0,0 +1,407 @@
+//! Lines whose width and opacity vary along their length.
+//!
+//! tiny-skia strokes a whole path at one width with one paint.  A ribbon is
+//! instead filled between smooth curves offset to either side of a polyline,
+//! so it can swell and taper, and is split into runs wherever its opacity
+//! changes, each filled with its own alpha.
+
+use clap::ValueEnum;
+use na::Vector2;
+use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Transform};
+
+/// Opacity is rounded to this many steps, so runs of similar opacity are filled as one
+/// path instead of leaving seams between every segment.
+pub const ALPHA_LEVELS: f64 = 64.;
+
+/// A point on a ribbon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RibbonPoint {
+    pub pos: Vector2<f64>,
+    pub width: f64,
+    pub alpha: f64,
+}
+
+/// What the width or opacity of a ribbon follows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// The same everywhere.
+    #[default]
+    Constant,
+    /// Rising from the minimum at both ends over the taper length.
+    Taper,
+    /// The speed of the flow, full at the full speed.
+    Speed,
+    /// A noise field.
+    Noise,
+}
+
+/// How width and opacity vary along ribbons.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RibbonStyle {
+    /// Smallest and largest width, in pixels.
+    pub width: [f64; 2],
+    pub width_profile: Profile,
+    /// Smallest and largest opacity, from 0 to 1.
+    pub alpha: [f64; 2],
+    pub alpha_profile: Profile,
+    /// Arc length over which tapered ribbons reach full width or opacity.
+    pub taper_length: f64,
+    /// Flow speed at which speed profiles are full.
+    pub full_speed: f64,
+}
+
+impl RibbonStyle {
+    /// Ribbons `width` wide and opaque throughout.
+    pub fn new(width: f64) -> Self {
+        RibbonStyle {
+            width: [width, width],
+            width_profile: Profile::Constant,
+            alpha: [1., 1.],
+            alpha_profile: Profile::Constant,
+            taper_length: 32.,
+            full_speed: 1.,
+        }
+    }
+
+    pub fn set_width(self, min: f64, max: f64, profile: Profile) -> Self {
+        RibbonStyle {
+            width: [min, max],
+            width_profile: profile,
+            ..self
+        }
+    }
+
+    pub fn set_alpha(self, min: f64, max: f64, profile: Profile) -> Self {
+        RibbonStyle {
+            alpha: [min, max],
+            alpha_profile: profile,
+            ..self
+        }
+    }
+
+    pub fn set_taper_length(self, taper_length: f64) -> Self {
+        RibbonStyle {
+            taper_length,
+            ..self
+        }
+    }
+
+    pub fn set_full_speed(self, full_speed: f64) -> Self {
+        RibbonStyle { full_speed, ..self }
+    }
+
+    /// Whether either profile follows noise.
+    pub fn uses_noise(&self) -> bool {
+        self.width_profile == Profile::Noise || self.alpha_profile == Profile::Noise
+    }
+
+    /// The width and opacity at each of `points`.  `speed` gives the flow speed at a
+    /// point and `noise` a value from -1 to 1, each only called for profiles that use it.
+    pub fn ribbon(
+        &self,
+        points: &[Vector2<f64>],
+        speed: impl Fn(Vector2<f64>) -> f64,
+        noise: impl Fn(Vector2<f64>) -> f64,
+    ) -> Vec<RibbonPoint> {
+        let arcs: Vec<f64> = points
+            .iter()
+            .scan((0., points.first().copied()), |(arc, prev), &p| {
+                *arc += prev.map_or(0., |prev| (p - prev).norm());
+                *prev = Some(p);
+                Some(*arc)
+            })
+            .collect();
+        let length = arcs.last().copied().unwrap_or(0.);
+        let factor = |profile, pos, arc: f64| match profile {
+            Profile::Constant => 1.,
+            Profile::Taper if self.taper_length <= 0. => 1.,
+            Profile::Taper => smoothstep(arc.min(length - arc) / self.taper_length),
+            Profile::Speed => (speed(pos) / self.full_speed).clamp(0., 1.),
+            Profile::Noise => ((noise(pos) + 1.) / 2.).clamp(0., 1.),
+        };
+        let lerp = |[min, max]: [f64; 2], t: f64| min + (max - min) * t;
+        points
+            .iter()
+            .zip(&arcs)
+            .map(|(&pos, &arc)| RibbonPoint {
+                pos,
+                width: lerp(self.width, factor(self.width_profile, pos, arc)),
+                alpha: lerp(self.alpha, factor(self.alpha_profile, pos, arc)),
+            })
+            .collect()
+    }
+}
+
+fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+/// The outline of `points` as closed paths with their opacity, one per run of segments
+/// whose opacity rounds to the same level.  Fully transparent runs are left out.
+pub fn ribbon_paths(points: &[RibbonPoint]) -> Vec<(tiny_skia::Path, f64)> {
+    let mut points = points.to_vec();
+    // repeated points have no direction to offset across
+    points.dedup_by(|b, a| (b.pos - a.pos).norm() < 1e-9);
+    if points.len() < 2 {
+        return vec![];
+    }
+
+    let last = points.len() - 1;
+    let mut normal = Vector2::zeros();
+    let (left, right): (Vec<_>, Vec<_>) = (0..points.len())
+        .map(|i| {
+            let tangent = points[(i + 1).min(last)].pos - points[i.saturating_sub(1)].pos;
+            // a reversal leaves no tangent, so keep the last normal
+            if let Some(tangent) = tangent.try_normalize(0.) {
+                normal = Vector2::new(-tangent.y, tangent.x);
+            }
+            let offset = normal * points[i].width.max(0.) / 2.;
+            (points[i].pos + offset, points[i].pos - offset)
+        })
+        .unzip();
+
+    let level = |i: usize| {
+        let alpha = (points[i].alpha + points[i + 1].alpha) / 2.;
+        (alpha.clamp(0., 1.) * ALPHA_LEVELS).round() as u32
+    };
+    let mut paths = vec![];
+    let mut start = 0;
+    for end in 1..=last {
+        if end < last && level(end) == level(start) {
+            continue;
+        }
+        let run_level = level(start);
+        if run_level > 0 {
+            let mut pb = PathBuilder::new();
+            pb.move_to(left[start].x as f32, left[start].y as f32);
+            spline_to(&mut pb, &left[start..=end]);
+            pb.line_to(right[end].x as f32, right[end].y as f32);
+            let back: Vec<_> = right[start..=end].iter().rev().copied().collect();
+            spline_to(&mut pb, &back);
+            pb.close();
+            if let Some(path) = pb.finish() {
+                paths.push((path, run_level as f64 / ALPHA_LEVELS));
+            }
+        }
+        start = end;
+    }
+    paths
+}
+
+/// Fill the ribbon through `points` with `color`, its alpha scaled by the ribbon's.
+pub fn fill_ribbon(
+    pixmap: &mut Pixmap,
+    points: &[RibbonPoint],
+    color: Color,
+    anti_alias: bool,
+    transform: Transform,
+) {
+    let mut paint = Paint {
+        anti_alias,
+        ..Default::default()
+    };
+    for (path, alpha) in ribbon_paths(points) {
+        let mut color = color;
+        color.apply_opacity(alpha as f32);
+        paint.set_color(color);
+        pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+    }
+}
+
+/// Extend `pb`, which must be at `points[0]`, with Catmull-Rom segments through the rest
+/// of `points`.
+fn spline_to(pb: &mut PathBuilder, points: &[Vector2<f64>]) {
+    for i in 1..points.len() {
+        let (p0, p1, p2) = (points[i.saturating_sub(2)], points[i - 1], points[i]);
+        let p3 = points[(i + 1).min(points.len() - 1)];
+        let c1 = p1 + (p2 - p0) / 6.;
+        let c2 = p2 - (p3 - p1) / 6.;
+        pb.cubic_to(
+            c1.x as f32,
+            c1.y as f32,
+            c2.x as f32,
+            c2.y as f32,
+            p2.x as f32,
+            p2.y as f32,
+        );
+    }
+}
+
+// Ribbon options to flatten into a binary's CLI.
+#[derive(clap::Args, Debug, Clone)]
+pub struct RibbonArgs {
+    /// line width in pixels, the widest lines get with a --width-profile
+    #[arg(long, default_value_t = 2., value_parser = parse_length)]
+    pub stroke_width: f64,
+
+    /// narrowest lines get with a --width-profile
+    #[arg(long, default_value_t = 0., value_parser = parse_length)]
+    pub stroke_width_min: f64,
+
+    /// what line width follows: nothing, tapering in and out over --taper-length, the
+    /// speed of the flow or a noise field
+    #[arg(long, value_enum, default_value_t = Profile::Constant)]
+    pub width_profile: Profile,
+
+    /// line opacity, the most opaque lines get with an --alpha-profile
+    #[arg(long, default_value_t = 1., value_parser = parse_alpha)]
+    pub stroke_alpha: f64,
+
+    /// most transparent lines get with an --alpha-profile
+    #[arg(long, default_value_t = 0., value_parser = parse_alpha)]
+    pub stroke_alpha_min: f64,
+
+    /// what line opacity follows, like --width-profile
+    #[arg(long, value_enum, default_value_t = Profile::Constant)]
+    pub alpha_profile: Profile,
+
+    /// length of tapers at each end of lines, in pixels
+    #[arg(long, default_value_t = 32., value_parser = parse_length)]
+    pub taper_length: f64,
+
+    /// flow speed at which speed profiles reach full width or opacity
+    #[arg(long, default_value_t = 1., value_parser = parse_speed)]
+    pub full_speed: f64,
+
+    /// noise units per pixel of the noise profile field
+    #[arg(long, default_value_t = 0.02, value_parser = parse_finite)]
+    pub profile_noise_scale: f64,
+}
+
+fn parse_finite(s: &str) -> Result<f64, String> {
+    let value: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(String::from("must be a finite number"))
+    }
+}
+
+fn parse_length(s: &str) -> Result<f64, String> {
+    let length = parse_finite(s)?;
+    if length >= 0. {
+        Ok(length)
+    } else {
+        Err(String::from("must not be negative"))
+    }
+}
+
+fn parse_alpha(s: &str) -> Result<f64, String> {
+    let alpha = parse_finite(s)?;
+    if (0. ..=1.).contains(&alpha) {
+        Ok(alpha)
+    } else {
+        Err(String::from("must be between 0 and 1"))
+    }
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    let speed = parse_finite(s)?;
+    if speed > 0. {
+        Ok(speed)
+    } else {
+        Err(String::from("must be a positive number"))
+    }
+}
+
+impl RibbonArgs {
+    pub fn style(&self) -> RibbonStyle {
+        RibbonStyle::new(self.stroke_width)
+            .set_width(self.stroke_width_min, self.stroke_width, self.width_profile)
+            .set_alpha(self.stroke_alpha_min, self.stroke_alpha, self.alpha_profile)
+            .set_taper_length(self.taper_length)
+            .set_full_speed(self.full_speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(n: usize) -> Vec<Vector2<f64>> {
+        (0..n).map(|i| Vector2::new(i as f64 * 10., 50.)).collect()
+    }
+
+    #[test]
+    fn test_ribbon_profiles() {
+        let points = line(11);
+        let style = RibbonStyle::new(8.)
+            .set_width(2., 8., Profile::Taper)
+            .set_alpha(0., 1., Profile::Speed)
+            .set_taper_length(30.)
+            .set_full_speed(2.);
+        let ribbon = style.ribbon(&points, |p| p.x / 50., |_| unreachable!());
+        let widths: Vec<_> = ribbon.iter().map(|p| p.width).collect();
+        assert_eq!(widths[0], 2.);
+        assert_eq!(widths[10], 2.);
+        assert!(widths[1] > 2. && widths[1] < widths[2] && widths[2] < 8.);
+        assert_eq!(widths[3..=7], [8.; 5]);
+        assert_eq!(ribbon[5].alpha, 0.5);
+        assert_eq!(ribbon[10].alpha, 1.);
+        assert!(!style.uses_noise());
+
+        let noisy = RibbonStyle::new(4.).set_alpha(0., 1., Profile::Noise);
+        assert!(noisy.uses_noise());
+        let ribbon = noisy.ribbon(&points, |_| unreachable!(), |_| 0.);
+        assert!(ribbon.iter().all(|p| p.width == 4. && p.alpha == 0.5));
+    }
+
+    #[test]
+    fn test_ribbon_args_reject_bad_values() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            ribbon: RibbonArgs,
+        }
+
+        for bad in [
+            ["--full-speed", "0"],
+            ["--stroke-alpha", "1.5"],
+            ["--stroke-alpha-min", "NaN"],
+            ["--stroke-width", "inf"],
+            ["--taper-length", "NaN"],
+            ["--profile-noise-scale", "inf"],
+        ] {
+            assert!(Cli::try_parse_from(["test", bad[0], bad[1]]).is_err());
+        }
+        let args = Cli::parse_from(["test", "--taper-length", "0", "--stroke-alpha", "0.5"]);
+        assert_eq!(args.ribbon.style().taper_length, 0.);
+    }
+
+    #[test]
+    fn test_ribbon_paths() {
+        let constant = RibbonStyle::new(6.).ribbon(&line(5), |_| 1., |_| 0.);
+        let paths = ribbon_paths(&constant);
+        assert_eq!(paths.len(), 1);
+        let bounds = paths[0].0.bounds();
+        assert_eq!(
+            [bounds.left(), bounds.top(), bounds.right(), bounds.bottom()],
+            [0., 47., 40., 53.]
+        );
+        assert_eq!(paths[0].1, 1.);
+
+        // fading in and out splits the ribbon into runs
+        let faded = RibbonStyle::new(6.)
+            .set_alpha(0., 1., Profile::Taper)
+            .set_taper_length(40.)
+            .ribbon(&line(9), |_| 1., |_| 0.);
+        let paths = ribbon_paths(&faded);
+        assert!(paths.len() > 2);
+        assert!(paths.iter().all(|(_, alpha)| *alpha > 0. && *alpha <= 1.));
+
+        let mut pixmap = Pixmap::new(100, 100).unwrap();
+        fill_ribbon(
+            &mut pixmap,
+            &constant,
+            Color::BLACK,
+            false,
+            Transform::identity(),
+        );
+        assert_eq!(pixmap.pixel(20, 50).unwrap().alpha(), 255);
+        assert_eq!(pixmap.pixel(20, 55).unwrap().alpha(), 0);
+        assert!(ribbon_paths(&constant[..1]).is_empty());
+    }
+}
@@ -1,48 +1,275 @@
 //! Utility library for working with files
 
-use std::num::ParseIntError;
+use std::{error::Error, fmt, fs, io, ops::Range, path::Path};
 
 use tiny_skia::Color;
 
-const DEFAULT_PALETTE: &'static str = include_str!("../assets/colors/ocaso.hex");
+mod convert;
+mod formats;
+mod gradient;
+mod harmony;
+mod ops;
+mod preview;
+mod registry;
 
-#[derive(Debug, PartialEq)]
-pub enum ParseHexColorError {
+pub use convert::{
+    color_to_linear, color_to_oklab, color_to_oklch, color_to_pixel, color_to_rgb, color_to_rgba,
+    linear_alpha_to_color, linear_to_color, linear_to_pixel, oklab_to_color, oklch_to_color,
+};
+pub use formats::{
+    parse_ase_palette, parse_gpl_palette, parse_jasc_palette, parse_palette, PaletteFormat,
+    ParsePaletteError,
+};
+pub use gradient::{Interpolation, PaletteGradient, PaletteLookup};
+pub use harmony::{generate_palette, Harmony, DEFAULT_HARMONY_COLORS};
+pub use ops::{PaletteOp, PaletteOpError, SortKey, SwatchRef};
+pub use preview::{render_palette_preview, PreviewOptions};
+pub use registry::{
+    builtin_palette, BuiltinPalette, PaletteArgs, PaletteSpec, BUILTIN_PALETTES,
+    DEFAULT_PALETTE_NAME,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseHexColorErrorKind {
+    /// Colors must have 3 (`rgb`), 6 (`rrggbb`) or 8 (`rrggbbaa`) digits.
     WrongColorStringLength {
-        input_str: String,
         actual_length: usize,
-        expected_length: usize,
     },
-    ParseIntError(ParseIntError),
+    InvalidHexDigit(char),
+}
+
+/// An error in a hex palette, with the location of the offending text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHexColorError {
+    pub kind: ParseHexColorErrorKind,
+    /// 1-indexed line number
+    pub line: usize,
+    /// 0-indexed, end-exclusive character span within the line
+    pub columns: Range<usize>,
+    /// full text of the offending line
+    pub input_str: String,
+}
+
+impl fmt::Display for ParseHexColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, ", self.line)?;
+        if self.columns.len() <= 1 {
+            write!(f, "column {}: ", self.columns.start + 1)?;
+        } else {
+            write!(
+                f,
+                "columns {}-{}: ",
+                self.columns.start + 1,
+                self.columns.end
+            )?;
+        }
+        match &self.kind {
+            ParseHexColorErrorKind::WrongColorStringLength { actual_length } => write!(
+                f,
+                "expected 3, 6 or 8 hex digits but found {}",
+                actual_length
+            )?,
+            ParseHexColorErrorKind::InvalidHexDigit(c) => write!(f, "invalid hex digit {:?}", c)?,
+        }
+        write!(f, " in {:?}", self.input_str)
+    }
+}
+
+impl Error for ParseHexColorError {}
+
+/// A single palette entry, with its name if the source format provides one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Swatch {
+    pub color: Color,
+    pub name: Option<String>,
+}
+
+/// A palette loaded from any supported file format.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Palette {
+    pub name: Option<String>,
+    pub swatches: Vec<Swatch>,
+}
+
+impl Palette {
+    pub fn colors(&self) -> Vec<Color> {
+        self.swatches.iter().map(|s| s.color).collect()
+    }
+
+    pub fn names(&self) -> Vec<Option<&str>> {
+        self.swatches.iter().map(|s| s.name.as_deref()).collect()
+    }
+}
+
+impl FromIterator<Color> for Palette {
+    fn from_iter<T: IntoIterator<Item = Color>>(iter: T) -> Self {
+        let swatches = iter
+            .into_iter()
+            .map(|color| Swatch { color, name: None })
+            .collect();
+        Palette {
+            name: None,
+            swatches,
+        }
+    }
 }
 
-impl From<ParseIntError> for ParseHexColorError {
-    fn from(value: ParseIntError) -> Self {
-        ParseHexColorError::ParseIntError(value)
+impl From<Palette> for Vec<Color> {
+    fn from(value: Palette) -> Self {
+        value.swatches.into_iter().map(|s| s.color).collect()
     }
 }
 
+#[derive(Debug)]
+pub enum LoadPaletteError {
+    Io(io::Error),
+    Parse(ParsePaletteError),
+    Op(PaletteOpError),
+}
+
+impl fmt::Display for LoadPaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read palette: {}", e),
+            Self::Parse(e) => write!(f, "could not parse palette: {}", e),
+            Self::Op(e) => write!(f, "could not apply palette op: {}", e),
+        }
+    }
+}
+
+impl Error for LoadPaletteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::Op(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for LoadPaletteError {
+    fn from(value: io::Error) -> Self {
+        LoadPaletteError::Io(value)
+    }
+}
+
+impl From<ParsePaletteError> for LoadPaletteError {
+    fn from(value: ParsePaletteError) -> Self {
+        LoadPaletteError::Parse(value)
+    }
+}
+
+impl From<PaletteOpError> for LoadPaletteError {
+    fn from(value: PaletteOpError) -> Self {
+        LoadPaletteError::Op(value)
+    }
+}
+
+/// Load a palette file, detecting its format from the extension or, failing
+/// that, from its contents.
+pub fn load_palette(path: impl AsRef<Path>) -> Result<Palette, LoadPaletteError> {
+    let bytes = fs::read(&path)?;
+    let format =
+        PaletteFormat::from_extension(&path).unwrap_or_else(|| PaletteFormat::detect(&bytes));
+    Ok(parse_palette(&bytes, format)?)
+}
+
+/// Parse a palette with one hex color per line.
+///
+/// Colors may be written as `rgb`, `rrggbb` or `rrggbbaa`, with or without a
+/// leading `#`.  Blank lines are skipped, and anything after a `;` is a comment.
 pub fn parse_hex_palette(s: &str) -> Result<Vec<Color>, ParseHexColorError> {
-    s.lines().map(parse_hex_color).collect()
+    s.lines()
+        .enumerate()
+        .filter_map(|(i, line)| parse_hex_line(line, i + 1).transpose())
+        .collect()
 }
 
+/// Parse a single hex color, e.g. `"#ff00aa"`.
 pub fn parse_hex_color(s: &str) -> Result<Color, ParseHexColorError> {
-    if s.len() != 6 {
-        return Err(ParseHexColorError::WrongColorStringLength {
-            input_str: String::from(s),
-            actual_length: s.len(),
-            expected_length: 6,
-        });
+    parse_hex_line(s, 1)?.ok_or_else(|| ParseHexColorError {
+        kind: ParseHexColorErrorKind::WrongColorStringLength { actual_length: 0 },
+        line: 1,
+        columns: 0..s.chars().count(),
+        input_str: String::from(s),
+    })
+}
+
+/// Parse one line of a hex palette.  Blank and comment lines yield `None`.
+fn parse_hex_line(line: &str, line_no: usize) -> Result<Option<Color>, ParseHexColorError> {
+    let content = line.split(';').next().unwrap_or_default();
+    let token = content.trim();
+    if token.is_empty() {
+        return Ok(None);
+    }
+    let offset = content.len() - content.trim_start().len();
+
+    parse_hex_token(token).map(Some).map_err(|(kind, bytes)| {
+        // convert byte offsets within the token to character columns within the line
+        let column = |i: usize| line[..offset + i].chars().count();
+        ParseHexColorError {
+            kind,
+            line: line_no,
+            columns: column(bytes.start)..column(bytes.end),
+            input_str: String::from(line),
+        }
+    })
+}
+
+/// Parse a trimmed color token.  Errors carry the byte span of the offending text.
+fn parse_hex_token(token: &str) -> Result<Color, (ParseHexColorErrorKind, Range<usize>)> {
+    let digits = token.strip_prefix('#').unwrap_or(token);
+    let prefix_len = token.len() - digits.len();
+    if let Some((i, c)) = digits.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        let start = prefix_len + i;
+        return Err((
+            ParseHexColorErrorKind::InvalidHexDigit(c),
+            start..start + c.len_utf8(),
+        ));
     }
 
-    let r = u8::from_str_radix(&s[0..2], 16)?;
-    let g = u8::from_str_radix(&s[2..4], 16)?;
-    let b = u8::from_str_radix(&s[4..6], 16)?;
-    Ok(Color::from_rgba8(r, g, b, 255))
+    // all digits are ASCII at this point
+    let nibble = |i: usize| (digits.as_bytes()[i] as char).to_digit(16).unwrap() as u8;
+    let byte = |i: usize| nibble(2 * i) << 4 | nibble(2 * i + 1);
+    match digits.len() {
+        3 => Ok(Color::from_rgba8(
+            nibble(0) * 17,
+            nibble(1) * 17,
+            nibble(2) * 17,
+            255,
+        )),
+        6 => Ok(Color::from_rgba8(byte(0), byte(1), byte(2), 255)),
+        8 => Ok(Color::from_rgba8(byte(0), byte(1), byte(2), byte(3))),
+        actual_length => Err((
+            ParseHexColorErrorKind::WrongColorStringLength { actual_length },
+            0..token.len(),
+        )),
+    }
+}
+
+/// Format a color as `rrggbb`, or `rrggbbaa` if it is not opaque.
+pub fn to_hex_color(color: &Color) -> String {
+    let c = color.to_color_u8();
+    if c.alpha() == 255 {
+        format!("{:02x}{:02x}{:02x}", c.red(), c.green(), c.blue())
+    } else {
+        format!(
+            "{:02x}{:02x}{:02x}{:02x}",
+            c.red(),
+            c.green(),
+            c.blue(),
+            c.alpha()
+        )
+    }
+}
+
+/// Format colors in the `.hex` palette format used in `assets/colors`.
+pub fn to_hex_palette(colors: &[Color]) -> String {
+    colors.iter().map(|c| to_hex_color(c) + "\n").collect()
 }
 
 pub fn get_default_palette() -> Vec<Color> {
-    parse_hex_palette(DEFAULT_PALETTE).unwrap()
+    PaletteSpec::default().load().unwrap().into()
 }
 
 #[cfg(test)]
@@ -78,11 +305,76 @@ ffffff";
             parse_hex_color("FF0000"),
             Ok(Color::from_rgba8(255, 0, 0, 255))
         );
+        assert_eq!(
+            parse_hex_color("#f0a"),
+            Ok(Color::from_rgba8(255, 0, 170, 255))
+        );
+        assert_eq!(
+            parse_hex_color("ff000080"),
+            Ok(Color::from_rgba8(255, 0, 0, 128))
+        );
+        assert!(parse_hex_color("").is_err());
+    }
+
+    #[test]
+    fn test_parse_hex_palette_tolerant() {
+        let palette_str = "; exported from somewhere
+#ff00aa
+  fff  ; shorthand
+
+00000080
+";
+        assert_eq!(
+            parse_hex_palette(palette_str),
+            Ok(vec![
+                Color::from_rgba8(255, 0, 170, 255),
+                Color::from_rgba8(255, 255, 255, 255),
+                Color::from_rgba8(0, 0, 0, 128),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_hex_palette_errors() {
+        let err = parse_hex_palette("000000\n\n  #ff00a\n").unwrap_err();
+        assert_eq!(
+            err,
+            ParseHexColorError {
+                kind: ParseHexColorErrorKind::WrongColorStringLength { actual_length: 5 },
+                line: 3,
+                columns: 2..8,
+                input_str: String::from("  #ff00a"),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 3, columns 3-8: expected 3, 6 or 8 hex digits but found 5 in \"  #ff00a\""
+        );
+
+        let err = parse_hex_palette("ff0000\nff0g00").unwrap_err();
+        assert_eq!(err.kind, ParseHexColorErrorKind::InvalidHexDigit('g'));
+        assert_eq!(err.line, 2);
+        assert_eq!(err.columns, 3..4);
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 4: invalid hex digit 'g' in \"ff0g00\""
+        );
+    }
+
+    #[test]
+    fn test_to_hex_palette() {
+        let colors = vec![
+            Color::from_rgba8(255, 0, 170, 255),
+            Color::from_rgba8(1, 2, 3, 128),
+        ];
+        let s = to_hex_palette(&colors);
+        assert_eq!(s, "ff00aa\n01020380\n");
+        assert_eq!(parse_hex_palette(&s), Ok(colors));
     }
 
     #[test]
     fn test_get_default_palette() {
         let palette = get_default_palette();
-        assert!(palette.len() > 0);
+        assert!(!palette.is_empty());
     }
 }
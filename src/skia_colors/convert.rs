@@ -0,0 +1,171 @@
+//! Conversions between `palette` color types, tiny-skia colors and `image` pixels.
+//!
+//! tiny-skia colors and `image` pixels hold sRGB-encoded components, while
+//! `LinSrgb` holds linear light.  Every crossing between the two goes through the
+//! sRGB transfer function, and 8-bit output is rounded rather than truncated.
+
+use image::{Rgb, Rgba};
+use palette::{
+    convert::IntoColorUnclamped, FloatComponent, FromColor, IntoComponent, LinSrgb, LinSrgba,
+    Oklab, Srgb,
+};
+use tiny_skia::{Color, PremultipliedColorU8};
+
+/// Clamp to [0, 1], mapping NaN to 0.
+fn unit(x: f32) -> f32 {
+    if x.is_nan() {
+        0.
+    } else {
+        x.clamp(0., 1.)
+    }
+}
+
+/// Encode a linear color as an opaque tiny-skia color.  Out of gamut components are clamped.
+pub fn linear_to_color<T: FloatComponent + IntoComponent<f32>>(color: LinSrgb<T>) -> Color {
+    linear_alpha_to_color(LinSrgba::from(color))
+}
+
+/// Encode a linear color with alpha as a tiny-skia color.  Out of gamut components are clamped.
+pub fn linear_alpha_to_color<T: FloatComponent + IntoComponent<f32>>(color: LinSrgba<T>) -> Color {
+    let color = color.into_format::<f32, f32>();
+    let encoded = Srgb::from_linear(color.color);
+    Color::from_rgba(
+        unit(encoded.red),
+        unit(encoded.green),
+        unit(encoded.blue),
+        unit(color.alpha),
+    )
+    .unwrap()
+}
+
+/// Decode a tiny-skia color into linear light.
+pub fn color_to_linear(color: &Color) -> LinSrgba {
+    let linear = Srgb::new(color.red(), color.green(), color.blue()).into_linear();
+    LinSrgba::new(linear.red, linear.green, linear.blue, color.alpha())
+}
+
+pub fn oklab_to_color(color: Oklab) -> Color {
+    let linear: LinSrgb = color.into_color_unclamped();
+    linear_to_color(linear)
+}
+
+/// OKLab coordinates of a tiny-skia color.  Alpha is dropped.
+pub fn color_to_oklab(color: &Color) -> Oklab {
+    Oklab::from_color(color_to_linear(color).color)
+}
+
+/// OKLCh coordinates of a tiny-skia color as `(lightness, chroma, hue in degrees)`.
+/// Alpha is dropped.
+pub fn color_to_oklch(color: &Color) -> (f32, f32, f32) {
+    let lab = color_to_oklab(color);
+    let hue = lab.b.atan2(lab.a).to_degrees().rem_euclid(360.);
+    (lab.l, lab.a.hypot(lab.b), hue)
+}
+
+fn oklch_to_linear(l: f32, c: f32, hue_degrees: f32) -> LinSrgb {
+    let h = hue_degrees.to_radians();
+    Oklab::new(l, c * h.cos(), c * h.sin()).into_color_unclamped()
+}
+
+fn in_gamut(c: &LinSrgb) -> bool {
+    // allow for round-off, so in-gamut colors survive a round trip untouched
+    let unit = -1e-4..=1. + 1e-4;
+    unit.contains(&c.red) && unit.contains(&c.green) && unit.contains(&c.blue)
+}
+
+/// Opaque color from OKLCh coordinates, reducing chroma until it fits in sRGB.
+pub fn oklch_to_color(l: f32, c: f32, hue_degrees: f32) -> Color {
+    let l = l.clamp(0., 1.);
+    let linear = oklch_to_linear(l, c, hue_degrees);
+    if in_gamut(&linear) {
+        return linear_to_color(linear);
+    }
+    let (mut lo, mut hi) = (0., c);
+    for _ in 0..16 {
+        let mid = (lo + hi) / 2.;
+        if in_gamut(&oklch_to_linear(l, mid, hue_degrees)) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    linear_to_color(oklch_to_linear(l, lo, hue_degrees))
+}
+
+/// Premultiplied 8-bit pixel, as stored in a `Pixmap`.
+pub fn color_to_pixel(color: &Color) -> PremultipliedColorU8 {
+    color.premultiply().to_color_u8()
+}
+
+/// Premultiplied 8-bit pixel for a linear color.
+pub fn linear_to_pixel<T: FloatComponent + IntoComponent<f32>>(
+    color: LinSrgb<T>,
+) -> PremultipliedColorU8 {
+    color_to_pixel(&linear_to_color(color))
+}
+
+/// Straight-alpha `image` pixel.
+pub fn color_to_rgba(color: &Color) -> Rgba<u8> {
+    let c = color.to_color_u8();
+    Rgba([c.red(), c.green(), c.blue(), c.alpha()])
+}
+
+/// `image` pixel with alpha dropped.
+pub fn color_to_rgb(color: &Color) -> Rgb<u8> {
+    let c = color.to_color_u8();
+    Rgb([c.red(), c.green(), c.blue()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skia_colors::BUILTIN_PALETTES;
+
+    #[test]
+    fn test_linear_to_color_encodes_srgb() {
+        // linear 0.5 is sRGB 0.735, not 0.5
+        let color = linear_to_color(LinSrgb::new(0.5f64, 0., 1.)).to_color_u8();
+        assert_eq!((color.red(), color.green(), color.blue()), (188, 0, 255));
+
+        // out of gamut and NaN components are clamped
+        let color = linear_to_color(LinSrgb::new(2., -1., f32::NAN)).to_color_u8();
+        assert_eq!((color.red(), color.green(), color.blue()), (255, 0, 0));
+    }
+
+    #[test]
+    fn test_bundled_palettes_round_trip() {
+        for builtin in BUILTIN_PALETTES {
+            for color in builtin.palette().colors() {
+                let linear = color_to_linear(&color);
+                assert_eq!(
+                    linear_to_color(linear.color).to_color_u8(),
+                    color.to_color_u8(),
+                    "{}",
+                    builtin.name
+                );
+                assert_eq!(
+                    oklab_to_color(color_to_oklab(&color)).to_color_u8(),
+                    color.to_color_u8(),
+                    "{}",
+                    builtin.name
+                );
+                let c = color.to_color_u8();
+                assert_eq!(
+                    color_to_rgba(&color),
+                    Rgba([c.red(), c.green(), c.blue(), 255])
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_color_to_pixel_premultiplies() {
+        let pixel = color_to_pixel(&Color::from_rgba8(255, 128, 0, 128));
+        assert_eq!(
+            (pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()),
+            (128, 64, 0, 128)
+        );
+        let color = linear_alpha_to_color(LinSrgba::new(1., 1., 1., 0.5));
+        assert_eq!(color_to_rgba(&color), Rgba([255, 255, 255, 128]));
+    }
+}
@@ -0,0 +1,553 @@
+//! Parsers for palette file formats other than bare `.hex` lists.
+//!
+//! Supported formats:
+//! - GIMP palettes (`.gpl`)
+//! - JASC / Paint Shop Pro palettes (`.pal`)
+//! - Adobe Swatch Exchange (`.ase`)
+
+use std::{error::Error, fmt, path::Path};
+
+use palette::{chromatic_adaptation::AdaptInto, white_point::D50, IntoColor, Lab, Srgb, Xyz};
+use tiny_skia::Color;
+
+use super::{parse_hex_palette, Palette, ParseHexColorError, Swatch};
+
+const GPL_HEADER: &str = "GIMP Palette";
+const JASC_HEADER: &str = "JASC-PAL";
+const ASE_SIGNATURE: &[u8] = b"ASEF";
+
+const ASE_BLOCK_GROUP_START: u16 = 0xc001;
+const ASE_BLOCK_GROUP_END: u16 = 0xc002;
+const ASE_BLOCK_COLOR_ENTRY: u16 = 0x0001;
+
+/// Palette file formats understood by [`parse_palette`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// One 6-digit hex color per line, as in `assets/colors`.
+    Hex,
+    /// GIMP palette, with optional per-swatch names.
+    Gpl,
+    /// JASC-PAL palette, as written by Paint Shop Pro and Lospec.
+    JascPal,
+    /// Adobe Swatch Exchange, a binary format with per-swatch names.
+    Ase,
+}
+
+impl PaletteFormat {
+    /// Guess the format from a file extension.  Returns `None` for unknown extensions.
+    pub fn from_extension(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "hex" | "txt" => Some(Self::Hex),
+            "gpl" => Some(Self::Gpl),
+            "pal" => Some(Self::JascPal),
+            "ase" => Some(Self::Ase),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from the leading bytes of a file.  Anything without a
+    /// recognizable header is assumed to be a hex list.
+    pub fn detect(bytes: &[u8]) -> Self {
+        // skip a UTF-8 byte order mark if present
+        let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+        if bytes.starts_with(ASE_SIGNATURE) {
+            Self::Ase
+        } else if bytes.starts_with(GPL_HEADER.as_bytes()) {
+            Self::Gpl
+        } else if bytes.starts_with(JASC_HEADER.as_bytes()) {
+            Self::JascPal
+        } else {
+            Self::Hex
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParsePaletteError {
+    Hex(ParseHexColorError),
+    InvalidUtf8,
+    MissingHeader {
+        expected: &'static str,
+    },
+    /// A line that could not be parsed.  `line` is 1-indexed.
+    InvalidLine {
+        line: usize,
+        contents: String,
+    },
+    ColorCountMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    UnexpectedEof,
+    InvalidUtf16Name,
+    UnsupportedColorModel(String),
+    NonFiniteComponent,
+}
+
+impl fmt::Display for ParsePaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hex(e) => e.fmt(f),
+            Self::InvalidUtf8 => write!(f, "file is not valid UTF-8"),
+            Self::MissingHeader { expected } => write!(f, "missing {:?} header", expected),
+            Self::InvalidLine { line, contents } => {
+                write!(f, "line {}: could not parse {:?}", line, contents)
+            }
+            Self::ColorCountMismatch { expected, actual } => write!(
+                f,
+                "header declares {} colors but {} were found",
+                expected, actual
+            ),
+            Self::UnexpectedEof => write!(f, "unexpected end of file"),
+            Self::InvalidUtf16Name => write!(f, "swatch name is not valid UTF-16"),
+            Self::UnsupportedColorModel(model) => {
+                write!(f, "unsupported color model {:?}", model)
+            }
+            Self::NonFiniteComponent => write!(f, "color component is NaN or infinite"),
+        }
+    }
+}
+
+impl Error for ParsePaletteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Hex(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseHexColorError> for ParsePaletteError {
+    fn from(value: ParseHexColorError) -> Self {
+        ParsePaletteError::Hex(value)
+    }
+}
+
+/// Parse a palette from raw file contents in the given format.
+pub fn parse_palette(bytes: &[u8], format: PaletteFormat) -> Result<Palette, ParsePaletteError> {
+    match format {
+        PaletteFormat::Ase => parse_ase_palette(bytes),
+        _ => {
+            let s = std::str::from_utf8(bytes).map_err(|_| ParsePaletteError::InvalidUtf8)?;
+            let s = s.strip_prefix('\u{feff}').unwrap_or(s);
+            match format {
+                PaletteFormat::Hex => Ok(parse_hex_palette(s)?.into_iter().collect()),
+                PaletteFormat::Gpl => parse_gpl_palette(s),
+                PaletteFormat::JascPal => parse_jasc_palette(s),
+                PaletteFormat::Ase => unreachable!(),
+            }
+        }
+    }
+}
+
+/// Parse a GIMP palette.
+///
+/// ```text
+/// GIMP Palette
+/// Name: Example
+/// Columns: 4
+/// # comment
+/// 255   0   0    Red
+///   0 255   0    Green
+/// ```
+pub fn parse_gpl_palette(s: &str) -> Result<Palette, ParsePaletteError> {
+    let mut lines = s.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.trim() == GPL_HEADER => {}
+        _ => {
+            return Err(ParsePaletteError::MissingHeader {
+                expected: GPL_HEADER,
+            })
+        }
+    }
+
+    let mut palette = Palette::default();
+    for (i, line) in lines {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix("Name:") {
+            palette.name = Some(String::from(name.trim()));
+            continue;
+        }
+        if trimmed.starts_with("Columns:") {
+            continue;
+        }
+
+        let invalid_line = || ParsePaletteError::InvalidLine {
+            line: i + 1,
+            contents: String::from(line),
+        };
+        let mut fields = trimmed.split_whitespace();
+        let mut channel = || -> Result<u8, ParsePaletteError> {
+            fields
+                .next()
+                .and_then(|f| f.parse().ok())
+                .ok_or_else(invalid_line)
+        };
+        let (r, g, b) = (channel()?, channel()?, channel()?);
+        // the name is whatever remains after the third channel
+        let name = fields.collect::<Vec<_>>().join(" ");
+        palette.swatches.push(Swatch {
+            color: Color::from_rgba8(r, g, b, 255),
+            name: (!name.is_empty()).then_some(name),
+        });
+    }
+    Ok(palette)
+}
+
+/// Parse a JASC-PAL palette.
+///
+/// ```text
+/// JASC-PAL
+/// 0100
+/// 2
+/// 255 0 0
+/// 0 255 0
+/// ```
+pub fn parse_jasc_palette(s: &str) -> Result<Palette, ParsePaletteError> {
+    let mut lines = s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+    match lines.next() {
+        Some((_, header)) if header.trim() == JASC_HEADER => {}
+        _ => {
+            return Err(ParsePaletteError::MissingHeader {
+                expected: JASC_HEADER,
+            })
+        }
+    }
+    // version line, always "0100" in practice
+    lines.next().ok_or(ParsePaletteError::UnexpectedEof)?;
+    let (i, count_line) = lines.next().ok_or(ParsePaletteError::UnexpectedEof)?;
+    let expected: usize =
+        count_line
+            .trim()
+            .parse()
+            .map_err(|_| ParsePaletteError::InvalidLine {
+                line: i + 1,
+                contents: String::from(count_line),
+            })?;
+
+    let mut palette = Palette::default();
+    for (i, line) in lines {
+        let channels: Vec<u8> = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| ParsePaletteError::InvalidLine {
+                line: i + 1,
+                contents: String::from(line),
+            })?;
+        // some writers append an alpha channel
+        if channels.len() != 3 && channels.len() != 4 {
+            return Err(ParsePaletteError::InvalidLine {
+                line: i + 1,
+                contents: String::from(line),
+            });
+        }
+        let color = Color::from_rgba8(channels[0], channels[1], channels[2], 255);
+        palette.swatches.push(Swatch { color, name: None });
+    }
+
+    if palette.swatches.len() != expected {
+        return Err(ParsePaletteError::ColorCountMismatch {
+            expected,
+            actual: palette.swatches.len(),
+        });
+    }
+    Ok(palette)
+}
+
+/// Parse an Adobe Swatch Exchange file.
+///
+/// Groups are flattened into a single palette; the name of the first group
+/// becomes the palette name.  RGB, CMYK, LAB and Gray color models are supported.
+pub fn parse_ase_palette(bytes: &[u8]) -> Result<Palette, ParsePaletteError> {
+    let mut reader = AseReader { bytes, pos: 0 };
+    if reader.take(4)? != ASE_SIGNATURE {
+        return Err(ParsePaletteError::MissingHeader { expected: "ASEF" });
+    }
+    let _version_major = reader.u16()?;
+    let _version_minor = reader.u16()?;
+    let n_blocks = reader.u32()?;
+
+    let mut palette = Palette::default();
+    for _ in 0..n_blocks {
+        let block_type = reader.u16()?;
+        let block_len = reader.u32()? as usize;
+        let mut block = AseReader {
+            bytes: reader.take(block_len)?,
+            pos: 0,
+        };
+        match block_type {
+            ASE_BLOCK_GROUP_START => {
+                let name = block.name()?;
+                if palette.name.is_none() && !name.is_empty() {
+                    palette.name = Some(name);
+                }
+            }
+            ASE_BLOCK_GROUP_END => {}
+            ASE_BLOCK_COLOR_ENTRY => {
+                let name = block.name()?;
+                let color = block.color()?;
+                palette.swatches.push(Swatch {
+                    color,
+                    name: (!name.is_empty()).then_some(name),
+                });
+            }
+            // unknown blocks are skipped, their length is known
+            _ => {}
+        }
+    }
+    Ok(palette)
+}
+
+/// Cursor over big-endian ASE data.
+struct AseReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> AseReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ParsePaletteError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .ok_or(ParsePaletteError::UnexpectedEof)?;
+        let out = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(ParsePaletteError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn u16(&mut self) -> Result<u16, ParsePaletteError> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ParsePaletteError> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// A color component, which must be finite.
+    fn f32(&mut self) -> Result<f32, ParsePaletteError> {
+        let v = f32::from_bits(self.u32()?);
+        if !v.is_finite() {
+            return Err(ParsePaletteError::NonFiniteComponent);
+        }
+        Ok(v)
+    }
+
+    /// Length-prefixed, null-terminated UTF-16BE string.
+    fn name(&mut self) -> Result<String, ParsePaletteError> {
+        let n_units = self.u16()? as usize;
+        let units = (0..n_units)
+            .map(|_| self.u16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let units = units.strip_suffix(&[0]).unwrap_or(&units);
+        String::from_utf16(units).map_err(|_| ParsePaletteError::InvalidUtf16Name)
+    }
+
+    fn color(&mut self) -> Result<Color, ParsePaletteError> {
+        let model = self.take(4)?;
+        let (r, g, b) = match model {
+            b"RGB " => (self.f32()?, self.f32()?, self.f32()?),
+            b"Gray" => {
+                let v = self.f32()?;
+                (v, v, v)
+            }
+            b"CMYK" => {
+                let (c, m, y, k) = (self.f32()?, self.f32()?, self.f32()?, self.f32()?);
+                (
+                    (1. - c) * (1. - k),
+                    (1. - m) * (1. - k),
+                    (1. - y) * (1. - k),
+                )
+            }
+            b"LAB " => {
+                // L is stored in [0, 1], a and b in their natural range
+                let (l, a, b) = (self.f32()?, self.f32()?, self.f32()?);
+                let xyz: Xyz<D50> = Lab::<D50>::with_wp(l * 100., a, b).into_color();
+                let rgb: Srgb = xyz.adapt_into();
+                (rgb.red, rgb.green, rgb.blue)
+            }
+            _ => {
+                return Err(ParsePaletteError::UnsupportedColorModel(
+                    String::from_utf8_lossy(model).into_owned(),
+                ))
+            }
+        };
+        // trailing color type (global / spot / normal) is not needed
+        let _color_type = self.u16()?;
+        // huge components can overflow in the conversions above
+        if ![r, g, b].iter().all(|v| v.is_finite()) {
+            return Err(ParsePaletteError::NonFiniteComponent);
+        }
+        Ok(Color::from_rgba(r.clamp(0., 1.), g.clamp(0., 1.), b.clamp(0., 1.), 1.).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tiny_skia::ColorU8;
+
+    use super::*;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(PaletteFormat::detect(b"ASEF\x00\x01"), PaletteFormat::Ase);
+        assert_eq!(
+            PaletteFormat::detect(b"GIMP Palette\nName: x\n"),
+            PaletteFormat::Gpl
+        );
+        assert_eq!(
+            PaletteFormat::detect(b"JASC-PAL\r\n0100\r\n"),
+            PaletteFormat::JascPal
+        );
+        assert_eq!(PaletteFormat::detect(b"ff0000\n"), PaletteFormat::Hex);
+        assert_eq!(
+            PaletteFormat::from_extension("foo/bar.GPL"),
+            Some(PaletteFormat::Gpl)
+        );
+        assert_eq!(PaletteFormat::from_extension("foo/bar"), None);
+    }
+
+    #[test]
+    fn test_parse_gpl_palette() {
+        let s = "GIMP Palette
+Name: Test Palette
+Columns: 2
+# a comment
+255   0   0\tRed
+  0 255   0\tBright Green
+  0   0 255
+";
+        let palette = parse_gpl_palette(s).unwrap();
+        assert_eq!(palette.name.as_deref(), Some("Test Palette"));
+        assert_eq!(
+            palette.colors(),
+            vec![
+                Color::from_rgba8(255, 0, 0, 255),
+                Color::from_rgba8(0, 255, 0, 255),
+                Color::from_rgba8(0, 0, 255, 255),
+            ]
+        );
+        assert_eq!(
+            palette.names(),
+            vec![Some("Red"), Some("Bright Green"), None]
+        );
+
+        assert_eq!(
+            parse_gpl_palette("GIMP Palette\n255 0\n"),
+            Err(ParsePaletteError::InvalidLine {
+                line: 2,
+                contents: String::from("255 0"),
+            })
+        );
+        assert!(parse_gpl_palette("255 0 0\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_jasc_palette() {
+        let s = "JASC-PAL\r\n0100\r\n2\r\n255 0 0\r\n0 0 255\r\n";
+        let palette = parse_jasc_palette(s).unwrap();
+        assert_eq!(
+            palette.colors(),
+            vec![
+                Color::from_rgba8(255, 0, 0, 255),
+                Color::from_rgba8(0, 0, 255, 255),
+            ]
+        );
+
+        assert_eq!(
+            parse_jasc_palette("JASC-PAL\n0100\n3\n255 0 0\n"),
+            Err(ParsePaletteError::ColorCountMismatch {
+                expected: 3,
+                actual: 1
+            })
+        );
+    }
+
+    fn ase_name(name: &str) -> Vec<u8> {
+        let units: Vec<u16> = name.encode_utf16().chain([0]).collect();
+        let mut out = (units.len() as u16).to_be_bytes().to_vec();
+        out.extend(units.iter().flat_map(|u| u.to_be_bytes()));
+        out
+    }
+
+    fn ase_block(block_type: u16, body: Vec<u8>) -> Vec<u8> {
+        let mut out = block_type.to_be_bytes().to_vec();
+        out.extend((body.len() as u32).to_be_bytes());
+        out.extend(body);
+        out
+    }
+
+    fn ase_color(name: &str, model: &[u8; 4], values: &[f32]) -> Vec<u8> {
+        let mut body = ase_name(name);
+        body.extend(model);
+        body.extend(values.iter().flat_map(|v| v.to_be_bytes()));
+        body.extend(2u16.to_be_bytes());
+        ase_block(ASE_BLOCK_COLOR_ENTRY, body)
+    }
+
+    fn ase_file(blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"ASEF".to_vec();
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend(0u16.to_be_bytes());
+        bytes.extend((blocks.len() as u32).to_be_bytes());
+        bytes.extend(blocks.concat());
+        bytes
+    }
+
+    #[test]
+    fn test_parse_ase_palette() {
+        let blocks = [
+            ase_block(ASE_BLOCK_GROUP_START, ase_name("Sunset")),
+            ase_color("Red", b"RGB ", &[1., 0., 0.]),
+            ase_color("Gray", b"Gray", &[0.]),
+            ase_color("", b"CMYK", &[0., 0., 0., 0.]),
+            ase_color("White", b"LAB ", &[1., 0., 0.]),
+            ase_block(ASE_BLOCK_GROUP_END, vec![]),
+        ];
+        let bytes = ase_file(&blocks);
+
+        assert_eq!(PaletteFormat::detect(&bytes), PaletteFormat::Ase);
+        let palette = parse_palette(&bytes, PaletteFormat::Ase).unwrap();
+        assert_eq!(palette.name.as_deref(), Some("Sunset"));
+        let colors: Vec<_> = palette.colors().iter().map(|c| c.to_color_u8()).collect();
+        assert_eq!(
+            colors,
+            vec![
+                ColorU8::from_rgba(255, 0, 0, 255),
+                ColorU8::from_rgba(0, 0, 0, 255),
+                ColorU8::from_rgba(255, 255, 255, 255),
+                ColorU8::from_rgba(255, 255, 255, 255),
+            ]
+        );
+        assert_eq!(
+            palette.names(),
+            vec![Some("Red"), Some("Gray"), None, Some("White")]
+        );
+
+        assert_eq!(
+            parse_ase_palette(&bytes[..bytes.len() - 4]),
+            Err(ParsePaletteError::UnexpectedEof)
+        );
+
+        let nan = ase_file(&[ase_color("Bad", b"RGB ", &[f32::NAN, 0., 0.])]);
+        assert_eq!(
+            parse_ase_palette(&nan),
+            Err(ParsePaletteError::NonFiniteComponent)
+        );
+        // CMYK products overflow for huge components
+        let huge = ase_file(&[ase_color("Bad", b"CMYK", &[f32::MAX, 0., 0., f32::MAX])]);
+        assert_eq!(
+            parse_ase_palette(&huge),
+            Err(ParsePaletteError::NonFiniteComponent)
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Smooth color ramps built from palettes.
+
+use std::f32::consts::{PI, TAU};
+
+use clap::ValueEnum;
+use palette::{convert::IntoColorUnclamped, LinSrgb, LinSrgba, Oklab};
+use tiny_skia::Color;
+
+use super::{color_to_linear, color_to_oklab, linear_alpha_to_color};
+
+/// Color space that gradient stops are blended in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Interpolation {
+    /// Physically linear light.  Midpoints tend to look too bright.
+    LinearSrgb,
+    /// Perceptually uniform lightness and color.
+    #[default]
+    Oklab,
+    /// Polar OKLab.  Hues travel around the shortest arc, keeping midpoints saturated.
+    Oklch,
+}
+
+/// How a scalar in [0, 1] is turned into a palette color.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PaletteLookup {
+    /// Pick the palette entry the value falls into, giving hard bands.
+    #[default]
+    Discrete,
+    /// Blend between neighboring entries.
+    Continuous,
+}
+
+/// A gradient through the colors of a palette.
+///
+/// Stops are evenly spaced by default.  [`PaletteGradient::perceptually_spaced`]
+/// instead places them by their OKLab distance, so equal steps in `t` give equal
+/// perceived changes in color.
+#[derive(Debug, Clone)]
+pub struct PaletteGradient {
+    colors: Vec<Color>,
+    /// stop colors in OKLab, with alpha
+    stops: Vec<(Oklab, f32)>,
+    positions: Vec<f32>,
+    pub interpolation: Interpolation,
+}
+
+impl PaletteGradient {
+    /// Panics if `colors` is empty.
+    pub fn new(colors: &[Color], interpolation: Interpolation) -> Self {
+        assert!(!colors.is_empty(), "a gradient needs at least one color");
+        let stops: Vec<_> = colors
+            .iter()
+            .map(|c| (color_to_oklab(c), c.alpha()))
+            .collect();
+        let last = (colors.len() - 1).max(1) as f32;
+        let positions = (0..colors.len()).map(|i| i as f32 / last).collect();
+        PaletteGradient {
+            colors: colors.to_vec(),
+            stops,
+            positions,
+            interpolation,
+        }
+    }
+
+    /// Space stops by the perceptual distance between neighboring colors.
+    pub fn perceptually_spaced(mut self) -> Self {
+        let mut total = 0.;
+        let mut distances = vec![0.];
+        for pair in self.stops.windows(2) {
+            let (a, b) = (pair[0].0, pair[1].0);
+            total += ((a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt();
+            distances.push(total);
+        }
+        // identical colors throughout: keep the even spacing
+        if total > 0. {
+            self.positions = distances.iter().map(|d| d / total).collect();
+        }
+        self
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// Palette entry for `t` in [0, 1], dividing the range into equal bands.
+    pub fn get_discrete(&self, t: f64) -> Color {
+        let n = self.colors.len();
+        let idx = (t.clamp(0., 1.) * n as f64) as usize;
+        self.colors[idx.min(n - 1)]
+    }
+
+    /// Blended color for `t` in [0, 1].  Values outside the range are clamped.
+    pub fn get(&self, t: f64) -> Color {
+        let t = (t as f32).clamp(0., 1.);
+        let hi = self
+            .positions
+            .iter()
+            .position(|&p| p >= t)
+            .unwrap_or(self.positions.len() - 1);
+        if hi == 0 {
+            return self.colors[0];
+        }
+        let lo = hi - 1;
+        let span = self.positions[hi] - self.positions[lo];
+        let s = if span > 0. {
+            (t - self.positions[lo]) / span
+        } else {
+            0.
+        };
+        self.mix(lo, hi, s)
+    }
+
+    pub fn lookup(&self, t: f64, lookup: PaletteLookup) -> Color {
+        match lookup {
+            PaletteLookup::Discrete => self.get_discrete(t),
+            PaletteLookup::Continuous => self.get(t),
+        }
+    }
+
+    fn mix(&self, lo: usize, hi: usize, s: f32) -> Color {
+        let lerp = |a: f32, b: f32| a + (b - a) * s;
+        let ((a, alpha_a), (b, alpha_b)) = (self.stops[lo], self.stops[hi]);
+        let linear: LinSrgb = match self.interpolation {
+            Interpolation::LinearSrgb => {
+                let a = color_to_linear(&self.colors[lo]);
+                let b = color_to_linear(&self.colors[hi]);
+                LinSrgb::new(
+                    lerp(a.red, b.red),
+                    lerp(a.green, b.green),
+                    lerp(a.blue, b.blue),
+                )
+            }
+            Interpolation::Oklab => {
+                Oklab::new(lerp(a.l, b.l), lerp(a.a, b.a), lerp(a.b, b.b)).into_color_unclamped()
+            }
+            Interpolation::Oklch => {
+                let (chroma_a, chroma_b) = (a.a.hypot(a.b), b.a.hypot(b.b));
+                let (mut hue_a, mut hue_b) = (a.b.atan2(a.a), b.b.atan2(b.a));
+                // grays have no meaningful hue, borrow the other end's
+                if chroma_a < 1e-4 {
+                    hue_a = hue_b;
+                }
+                if chroma_b < 1e-4 {
+                    hue_b = hue_a;
+                }
+                let mut delta = (hue_b - hue_a) % TAU;
+                if delta > PI {
+                    delta -= TAU;
+                } else if delta < -PI {
+                    delta += TAU;
+                }
+                let chroma = lerp(chroma_a, chroma_b);
+                let hue = hue_a + delta * s;
+                Oklab::new(lerp(a.l, b.l), chroma * hue.cos(), chroma * hue.sin())
+                    .into_color_unclamped()
+            }
+        };
+        let alpha = lerp(alpha_a, alpha_b);
+        linear_alpha_to_color(LinSrgba::new(linear.red, linear.green, linear.blue, alpha))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tiny_skia::ColorU8;
+
+    use super::*;
+
+    fn u8s(c: Color) -> ColorU8 {
+        c.to_color_u8()
+    }
+
+    #[test]
+    fn test_gradient_endpoints() {
+        let colors = [
+            Color::from_rgba8(20, 12, 28, 255),
+            Color::from_rgba8(208, 70, 72, 255),
+            Color::from_rgba8(222, 238, 214, 255),
+        ];
+        for interpolation in [
+            Interpolation::LinearSrgb,
+            Interpolation::Oklab,
+            Interpolation::Oklch,
+        ] {
+            let gradient = PaletteGradient::new(&colors, interpolation);
+            assert_eq!(u8s(gradient.get(0.)), u8s(colors[0]));
+            assert_eq!(u8s(gradient.get(0.5)), u8s(colors[1]));
+            assert_eq!(u8s(gradient.get(1.)), u8s(colors[2]));
+            assert_eq!(u8s(gradient.get(2.)), u8s(colors[2]));
+        }
+    }
+
+    #[test]
+    fn test_gradient_discrete() {
+        let colors = [
+            Color::from_rgba8(0, 0, 0, 255),
+            Color::from_rgba8(255, 0, 0, 255),
+            Color::from_rgba8(255, 255, 255, 255),
+        ];
+        let gradient = PaletteGradient::new(&colors, Interpolation::Oklab);
+        assert_eq!(gradient.get_discrete(0.), colors[0]);
+        assert_eq!(gradient.get_discrete(0.4), colors[1]);
+        assert_eq!(gradient.get_discrete(0.99), colors[2]);
+        assert_eq!(gradient.get_discrete(1.), colors[2]);
+        assert_eq!(gradient.lookup(0.5, PaletteLookup::Discrete), colors[1]);
+    }
+
+    #[test]
+    fn test_gradient_oklch_shortest_hue() {
+        // red to magenta should pass through pink, not around through green
+        let colors = [
+            Color::from_rgba8(255, 0, 0, 255),
+            Color::from_rgba8(255, 0, 255, 255),
+        ];
+        let mid = u8s(PaletteGradient::new(&colors, Interpolation::Oklch).get(0.5));
+        assert!(mid.green() < 64, "{:?}", mid);
+        assert!(mid.red() > 192, "{:?}", mid);
+    }
+
+    #[test]
+    fn test_gradient_perceptually_spaced() {
+        let colors = [
+            Color::from_rgba8(0, 0, 0, 255),
+            Color::from_rgba8(8, 8, 8, 255),
+            Color::from_rgba8(255, 255, 255, 255),
+        ];
+        let gradient = PaletteGradient::new(&colors, Interpolation::Oklab).perceptually_spaced();
+        // the two near-blacks are close together, so the midpoint is well past them
+        let mid = u8s(gradient.get(0.5));
+        assert!(mid.red() > 64, "{:?}", mid);
+        assert_eq!(u8s(gradient.get(1.)), u8s(colors[2]));
+    }
+}
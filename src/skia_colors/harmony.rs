@@ -0,0 +1,167 @@
+//! Seeded palette generation from color harmony rules.
+//!
+//! Colors are chosen in OKLCh, so that hue offsets such as "opposite" or "a third
+//! of the way around" match perceived hue, and lightness ramps look even.
+
+use std::f32::consts::TAU;
+
+use clap::ValueEnum;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use tiny_skia::Color;
+
+use super::{oklch_to_color, Palette};
+
+pub const DEFAULT_HARMONY_COLORS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Harmony {
+    /// Base hue and its opposite.
+    Complementary,
+    /// Neighboring hues within a 60° arc.
+    Analogous,
+    /// Three hues 120° apart.
+    Triadic,
+    /// Base hue and the two neighbors of its opposite.
+    SplitComplementary,
+    /// Four hues 90° apart.
+    Tetradic,
+    /// `a + b * cos(2π(c * t + d))` over OKLCh channels, after Inigo Quilez.
+    Cosine,
+}
+
+impl Harmony {
+    /// Hue offsets in degrees from the base hue.
+    fn hue_offsets(&self) -> &'static [f32] {
+        match self {
+            Self::Complementary => &[0., 180.],
+            Self::Analogous => &[-30., 0., 30.],
+            Self::Triadic => &[0., 120., 240.],
+            Self::SplitComplementary => &[0., 150., 210.],
+            Self::Tetradic => &[0., 90., 180., 270.],
+            Self::Cosine => &[0.],
+        }
+    }
+}
+
+/// Generate `n_colors` colors following `harmony`, ordered from dark to light.
+pub fn generate_palette(harmony: Harmony, n_colors: usize, rng: &mut ChaCha8Rng) -> Palette {
+    let colors = match harmony {
+        Harmony::Cosine => cosine_colors(n_colors, rng),
+        _ => harmony_colors(harmony, n_colors, rng),
+    };
+    let mut palette: Palette = colors.into_iter().collect();
+    palette.name = Some(harmony.to_possible_value().unwrap().get_name().to_string());
+    palette
+}
+
+fn harmony_colors(harmony: Harmony, n_colors: usize, rng: &mut ChaCha8Rng) -> Vec<Color> {
+    let base_hue: f32 = rng.gen_range(0. ..360.);
+    let chroma: f32 = rng.gen_range(0.08..0.18);
+    let (l_min, l_max): (f32, f32) = (rng.gen_range(0.2..0.35), rng.gen_range(0.8..0.95));
+    let offsets = harmony.hue_offsets();
+
+    (0..n_colors)
+        .map(|i| {
+            let t = if n_colors > 1 {
+                i as f32 / (n_colors - 1) as f32
+            } else {
+                0.5
+            };
+            let offset = match harmony {
+                // spread evenly across the arc instead of snapping to three hues
+                Harmony::Analogous => offsets[0] + (offsets[2] - offsets[0]) * t,
+                _ => offsets[i % offsets.len()],
+            };
+            let jitter: f32 = rng.gen_range(-5. ..5.);
+            let l = l_min + (l_max - l_min) * t;
+            // pull chroma in at the ends of the lightness ramp, where the gamut narrows
+            let c = chroma * (1. - (2. * t - 1.).powi(2) * 0.5);
+            oklch_to_color(l, c, base_hue + offset + jitter)
+        })
+        .collect()
+}
+
+fn cosine_colors(n_colors: usize, rng: &mut ChaCha8Rng) -> Vec<Color> {
+    // one (a, b, c, d) set per channel: lightness, chroma, hue
+    let l = [
+        rng.gen_range(0.45..0.65),
+        rng.gen_range(0.15..0.3),
+        rng.gen_range(0.3..0.6),
+        rng.gen_range(0. ..1.),
+    ];
+    let c = [
+        rng.gen_range(0.08..0.14),
+        rng.gen_range(0.02..0.06),
+        rng.gen_range(0.5..1.5),
+        rng.gen_range(0. ..1.),
+    ];
+    let hue_frequency: f32 = rng.gen_range(0.2..0.8);
+    let hue_phase: f32 = rng.gen_range(0. ..1.);
+    let cosine = |[a, b, c, d]: [f32; 4], t: f32| a + b * (TAU * (c * t + d)).cos();
+
+    let mut colors: Vec<_> = (0..n_colors)
+        .map(|i| i as f32 / n_colors.max(1) as f32)
+        .map(|t| {
+            let hue = 360. * (hue_frequency * t + hue_phase);
+            (cosine(l, t), cosine(c, t).max(0.), hue)
+        })
+        .collect();
+    colors.sort_by(|a, b| a.0.total_cmp(&b.0));
+    colors
+        .into_iter()
+        .map(|(l, c, h)| oklch_to_color(l, c, h))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::skia_colors::{color_to_oklab, color_to_oklch};
+
+    fn hue_degrees(color: &Color) -> f32 {
+        color_to_oklch(color).2
+    }
+
+    #[test]
+    fn test_generate_palette_is_reproducible() {
+        for harmony in Harmony::value_variants() {
+            let a = generate_palette(*harmony, 6, &mut ChaCha8Rng::seed_from_u64(7));
+            let b = generate_palette(*harmony, 6, &mut ChaCha8Rng::seed_from_u64(7));
+            assert_eq!(a, b);
+            assert_eq!(a.swatches.len(), 6);
+            let c = generate_palette(*harmony, 6, &mut ChaCha8Rng::seed_from_u64(8));
+            assert_ne!(a, c);
+        }
+    }
+
+    #[test]
+    fn test_generate_palette_ordered_by_lightness() {
+        for harmony in Harmony::value_variants() {
+            let palette = generate_palette(*harmony, 5, &mut ChaCha8Rng::seed_from_u64(1));
+            let lightness: Vec<_> = palette
+                .colors()
+                .iter()
+                .map(|c| color_to_oklab(c).l)
+                .collect();
+            assert!(
+                lightness.windows(2).all(|w| w[0] <= w[1] + 1e-3),
+                "{:?}: {:?}",
+                harmony,
+                lightness
+            );
+        }
+    }
+
+    #[test]
+    fn test_complementary_hues() {
+        let palette =
+            generate_palette(Harmony::Complementary, 4, &mut ChaCha8Rng::seed_from_u64(3));
+        let colors = palette.colors();
+        let delta = (hue_degrees(&colors[1]) - hue_degrees(&colors[2])).rem_euclid(360.);
+        // 180° apart, give or take jitter and gamut mapping
+        assert!((delta - 180.).abs() < 20., "{}", delta);
+    }
+}
@@ -0,0 +1,373 @@
+//! Operations that derive palette variants, selected on the command line with
+//! `--palette-op`, e.g. `--palette-op sort:lum --palette-op reverse`.
+//!
+//! Lightness and chroma adjustments happen in OKLCh, so lightening keeps hues
+//! intact and steps look even across the palette.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use clap::ValueEnum;
+use tiny_skia::Color;
+
+use super::{color_to_oklch, oklch_to_color, Palette, Swatch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Perceived lightness, dark to light.
+    #[value(alias = "lightness")]
+    Lum,
+    /// Hue angle, starting from red.  Grays come first, dark to light.
+    Hue,
+    /// Colorfulness, grays first.
+    Chroma,
+}
+
+/// Grays have no meaningful hue.
+const GRAY_CHROMA: f32 = 0.02;
+
+impl SortKey {
+    fn key(&self, color: &Color) -> (f32, f32) {
+        let (l, c, h) = color_to_oklch(color);
+        match self {
+            Self::Lum => (l, 0.),
+            Self::Hue if c < GRAY_CHROMA => (-1., l),
+            Self::Hue => (h, l),
+            Self::Chroma => (c, l),
+        }
+    }
+}
+
+/// A swatch picked by `pick:`, either by 0-based index or by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwatchRef {
+    Index(usize),
+    Name(String),
+}
+
+impl fmt::Display for SwatchRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(i) => write!(f, "{}", i),
+            Self::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteOp {
+    /// `sort:lum`, `sort:hue` or `sort:chroma`
+    Sort(SortKey),
+    /// `reverse`
+    Reverse,
+    /// `rotate:n` moves the first `n` colors to the end; negative `n` rotates the other way.
+    Rotate(isize),
+    /// `lighten:x` adds `x` to OKLCh lightness, `darken:x` subtracts it.
+    Lighten(f32),
+    /// `saturate:x` scales chroma by `1 + x`, `desaturate:x` by `1 - x`.
+    Saturate(f32),
+    /// `every:n[:offset]` keeps every `n`th color, starting at `offset`.
+    Every { step: usize, offset: usize },
+    /// `pick:0,3,name` keeps the listed colors, in the listed order.
+    Pick(Vec<SwatchRef>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteOpError {
+    /// A `pick:` entry that is out of range or names no swatch.
+    UnknownSwatch(SwatchRef),
+    /// The operation left no colors.
+    Empty(String),
+}
+
+impl fmt::Display for PaletteOpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownSwatch(swatch) => write!(f, "palette has no swatch '{}'", swatch),
+            Self::Empty(op) => write!(f, "'{}' leaves no colors in the palette", op),
+        }
+    }
+}
+
+impl Error for PaletteOpError {}
+
+impl PaletteOp {
+    pub fn apply(&self, mut palette: Palette) -> Result<Palette, PaletteOpError> {
+        let swatches = &mut palette.swatches;
+        match self {
+            Self::Sort(key) => {
+                swatches.sort_by(|a, b| {
+                    let (a, b) = (key.key(&a.color), key.key(&b.color));
+                    a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+                });
+            }
+            Self::Reverse => swatches.reverse(),
+            Self::Rotate(n) => {
+                if !swatches.is_empty() {
+                    let n = n.rem_euclid(swatches.len() as isize) as usize;
+                    swatches.rotate_left(n);
+                }
+            }
+            Self::Lighten(amount) => {
+                adjust_oklch(swatches, |l, c| (l + amount, c));
+            }
+            Self::Saturate(amount) => {
+                adjust_oklch(swatches, |l, c| (l, c * (1. + amount).max(0.)));
+            }
+            Self::Every { step, offset } => {
+                *swatches = swatches
+                    .iter()
+                    .skip(*offset)
+                    .step_by(*step)
+                    .cloned()
+                    .collect();
+            }
+            Self::Pick(refs) => {
+                *swatches = refs
+                    .iter()
+                    .map(|r| {
+                        let found = match r {
+                            SwatchRef::Index(i) => swatches.get(*i),
+                            SwatchRef::Name(name) => swatches
+                                .iter()
+                                .find(|s| s.name.as_deref() == Some(name.as_str())),
+                        };
+                        found
+                            .cloned()
+                            .ok_or_else(|| PaletteOpError::UnknownSwatch(r.clone()))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+        }
+        if swatches.is_empty() {
+            return Err(PaletteOpError::Empty(self.to_string()));
+        }
+        Ok(palette)
+    }
+}
+
+fn adjust_oklch(swatches: &mut [Swatch], f: impl Fn(f32, f32) -> (f32, f32)) {
+    for swatch in swatches {
+        let (l, c, h) = color_to_oklch(&swatch.color);
+        let (l, c) = f(l, c);
+        let alpha = swatch.color.alpha();
+        swatch.color = oklch_to_color(l, c, h);
+        swatch.color.set_alpha(alpha);
+    }
+}
+
+impl Palette {
+    /// Apply operations in order.
+    pub fn apply_ops(self, ops: &[PaletteOp]) -> Result<Palette, PaletteOpError> {
+        ops.iter().try_fold(self, |palette, op| op.apply(palette))
+    }
+}
+
+fn parse_arg<T: FromStr>(op: &str, arg: Option<&str>) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("'{}' needs an argument, e.g. '{}:2'", op, op))?;
+    arg.parse()
+        .map_err(|_| format!("invalid argument '{}' for '{}'", arg, op))
+}
+
+impl FromStr for PaletteOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (op, arg) = match s.split_once(':') {
+            Some((op, arg)) => (op, Some(arg)),
+            None => (s, None),
+        };
+        let amount = |arg| -> Result<f32, String> {
+            let amount: f32 = parse_arg(op, arg)?;
+            if amount.is_finite() {
+                Ok(amount)
+            } else {
+                Err(format!("invalid argument '{}' for '{}'", amount, op))
+            }
+        };
+        let parsed = match op {
+            "sort" => {
+                let key = arg.ok_or("'sort' needs a key: lum, hue or chroma")?;
+                Self::Sort(SortKey::from_str(key, true).map_err(|_| {
+                    format!("unknown sort key '{}', expected lum, hue or chroma", key)
+                })?)
+            }
+            "reverse" => {
+                if arg.is_some() {
+                    return Err(String::from("'reverse' takes no argument"));
+                }
+                Self::Reverse
+            }
+            "rotate" => Self::Rotate(parse_arg(op, arg)?),
+            "lighten" => Self::Lighten(amount(arg)?),
+            "darken" => Self::Lighten(-amount(arg)?),
+            "saturate" => Self::Saturate(amount(arg)?),
+            "desaturate" => Self::Saturate(-amount(arg)?),
+            "every" => {
+                let (step, offset) = match arg.and_then(|a| a.split_once(':')) {
+                    Some((step, offset)) => (Some(step), parse_arg(op, Some(offset))?),
+                    None => (arg, 0),
+                };
+                let step = parse_arg(op, step)?;
+                if step == 0 {
+                    return Err(String::from("'every' needs a step of at least 1"));
+                }
+                Self::Every { step, offset }
+            }
+            "pick" => {
+                let arg = arg.unwrap_or_default();
+                if arg.is_empty() {
+                    return Err(String::from("'pick' needs a list of indices or names"));
+                }
+                Self::Pick(
+                    arg.split(',')
+                        .map(|r| match r.trim().parse() {
+                            Ok(i) => SwatchRef::Index(i),
+                            Err(_) => SwatchRef::Name(r.trim().to_string()),
+                        })
+                        .collect(),
+                )
+            }
+            _ => {
+                return Err(format!(
+                    "unknown palette op '{}', expected one of: sort, reverse, rotate, lighten, \
+                     darken, saturate, desaturate, every, pick",
+                    op
+                ))
+            }
+        };
+        Ok(parsed)
+    }
+}
+
+impl fmt::Display for PaletteOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sort(key) => write!(f, "sort:{}", key.to_possible_value().unwrap().get_name()),
+            Self::Reverse => write!(f, "reverse"),
+            Self::Rotate(n) => write!(f, "rotate:{}", n),
+            Self::Lighten(x) if *x < 0. => write!(f, "darken:{}", -x),
+            Self::Lighten(x) => write!(f, "lighten:{}", x),
+            Self::Saturate(x) if *x < 0. => write!(f, "desaturate:{}", -x),
+            Self::Saturate(x) => write!(f, "saturate:{}", x),
+            Self::Every { step, offset: 0 } => write!(f, "every:{}", step),
+            Self::Every { step, offset } => write!(f, "every:{}:{}", step, offset),
+            Self::Pick(refs) => {
+                let refs: Vec<_> = refs.iter().map(|r| r.to_string()).collect();
+                write!(f, "pick:{}", refs.join(","))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(v: u8) -> Color {
+        Color::from_rgba8(v, v, v, 255)
+    }
+
+    fn named(colors: &[(&str, Color)]) -> Palette {
+        Palette {
+            name: None,
+            swatches: colors
+                .iter()
+                .map(|(name, color)| Swatch {
+                    color: *color,
+                    name: Some(name.to_string()),
+                })
+                .collect(),
+        }
+    }
+
+    fn ops(ops: &[&str]) -> Vec<PaletteOp> {
+        ops.iter().map(|op| op.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_palette_op_from_str() {
+        for op in [
+            "sort:lum",
+            "sort:hue",
+            "reverse",
+            "rotate:-2",
+            "lighten:0.1",
+            "darken:0.25",
+            "desaturate:0.5",
+            "every:2",
+            "every:3:1",
+            "pick:0,3,sky blue",
+        ] {
+            assert_eq!(op.parse::<PaletteOp>().unwrap().to_string(), op);
+        }
+        assert_eq!("sort:lightness".parse(), Ok(PaletteOp::Sort(SortKey::Lum)));
+        for bad in [
+            "sort",
+            "sort:size",
+            "rotate",
+            "lighten:x",
+            "every:0",
+            "pick:",
+            "nope",
+        ] {
+            assert!(bad.parse::<PaletteOp>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_reorder_ops() {
+        let palette: Palette = [gray(200), gray(0), gray(100)].into_iter().collect();
+        let sorted = palette.clone().apply_ops(&ops(&["sort:lum"])).unwrap();
+        assert_eq!(sorted.colors(), [gray(0), gray(100), gray(200)]);
+        let reversed = palette.clone().apply_ops(&ops(&["sort:lum", "reverse"]));
+        assert_eq!(reversed.unwrap().colors(), [gray(200), gray(100), gray(0)]);
+        let rotated = palette.clone().apply_ops(&ops(&["rotate:1"])).unwrap();
+        assert_eq!(rotated.colors(), [gray(0), gray(100), gray(200)]);
+        let rotated = palette.apply_ops(&ops(&["rotate:-4"])).unwrap();
+        assert_eq!(rotated.colors(), [gray(100), gray(200), gray(0)]);
+    }
+
+    #[test]
+    fn test_oklch_adjustments() {
+        let red = Color::from_rgba8(200, 40, 40, 255);
+        let palette: Palette = [red].into_iter().collect();
+        let (l, c, h) = color_to_oklch(&red);
+
+        let lighter = palette.clone().apply_ops(&ops(&["lighten:0.1"])).unwrap();
+        let (l2, _, h2) = color_to_oklch(&lighter.colors()[0]);
+        assert!((l2 - l - 0.1).abs() < 0.01, "{} -> {}", l, l2);
+        assert!((h2 - h).abs() < 2., "{} -> {}", h, h2);
+
+        let gray = palette.clone().apply_ops(&ops(&["desaturate:1"])).unwrap();
+        let gray = gray.colors()[0].to_color_u8();
+        assert_eq!(gray.red(), gray.green());
+        assert_eq!(gray.green(), gray.blue());
+
+        let duller = palette.apply_ops(&ops(&["desaturate:0.5"])).unwrap();
+        let (_, c2, _) = color_to_oklch(&duller.colors()[0]);
+        assert!((c2 - c / 2.).abs() < 0.01, "{} -> {}", c, c2);
+    }
+
+    #[test]
+    fn test_subset_ops() {
+        let palette = named(&[
+            ("ink", gray(0)),
+            ("lead", gray(80)),
+            ("ash", gray(160)),
+            ("bone", gray(240)),
+        ]);
+        let every = palette.clone().apply_ops(&ops(&["every:2:1"])).unwrap();
+        assert_eq!(every.names(), [Some("lead"), Some("bone")]);
+        let picked = palette.clone().apply_ops(&ops(&["pick:bone,0"])).unwrap();
+        assert_eq!(picked.names(), [Some("bone"), Some("ink")]);
+
+        assert_eq!(
+            palette.clone().apply_ops(&ops(&["pick:4"])),
+            Err(PaletteOpError::UnknownSwatch(SwatchRef::Index(4)))
+        );
+        assert!(matches!(
+            palette.apply_ops(&ops(&["every:1:10"])),
+            Err(PaletteOpError::Empty(_))
+        ));
+    }
+}
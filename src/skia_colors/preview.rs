@@ -0,0 +1,253 @@
+//! Contact sheets showing the contents of palettes.
+//!
+//! Each palette is drawn as a row of swatches labeled with their hex values,
+//! followed by a strip of the continuous gradient through the same colors.
+
+use tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+
+use super::{color_to_oklab, to_hex_color, Interpolation, Palette, PaletteGradient};
+
+/// Glyphs are 3x5 cells, one row per entry with the leftmost cell in bit 2.
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+fn glyph(c: char) -> Option<[u8; 5]> {
+    let rows = match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'a' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'b' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'c' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'd' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'e' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'f' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PreviewOptions {
+    pub swatch_width: u32,
+    pub swatch_height: u32,
+    /// Height of the gradient strip below each row of swatches.
+    pub gradient_height: u32,
+    /// Space around the sheet and between palettes.
+    pub padding: u32,
+    pub interpolation: Interpolation,
+    pub background: Color,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        PreviewOptions {
+            swatch_width: 96,
+            swatch_height: 64,
+            gradient_height: 24,
+            padding: 8,
+            interpolation: Interpolation::default(),
+            background: Color::from_rgba8(32, 32, 32, 255),
+        }
+    }
+}
+
+impl PreviewOptions {
+    /// Size of one font cell in pixels, so that a `#rrggbbaa` label fits in a swatch.
+    fn font_scale(&self) -> u32 {
+        let label_cells = 9 * (GLYPH_WIDTH + 1);
+        (self.swatch_width.saturating_sub(4) / label_cells).max(1)
+    }
+}
+
+fn fill_rect(pixmap: &mut Pixmap, x: u32, y: u32, w: u32, h: u32, color: Color) {
+    let Some(rect) = Rect::from_xywh(x as f32, y as f32, w as f32, h as f32) else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+}
+
+/// Checkerboard behind translucent colors, so their alpha is visible.
+fn fill_checkerboard(pixmap: &mut Pixmap, x: u32, y: u32, w: u32, h: u32) {
+    const CELL: u32 = 8;
+    let light = Color::from_rgba8(204, 204, 204, 255);
+    let dark = Color::from_rgba8(153, 153, 153, 255);
+    for cy in (0..h).step_by(CELL as usize) {
+        for cx in (0..w).step_by(CELL as usize) {
+            let color = if (cx / CELL + cy / CELL) % 2 == 0 {
+                light
+            } else {
+                dark
+            };
+            let (cw, ch) = (CELL.min(w - cx), CELL.min(h - cy));
+            fill_rect(pixmap, x + cx, y + cy, cw, ch, color);
+        }
+    }
+}
+
+/// Draw `text` with its top left corner at (x, y).  Unsupported characters are skipped.
+fn draw_text(pixmap: &mut Pixmap, text: &str, x: u32, y: u32, scale: u32, color: Color) {
+    for (i, rows) in text.chars().filter_map(glyph).enumerate() {
+        let gx = x + i as u32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    let (px, py) = (gx + col * scale, y + row as u32 * scale);
+                    fill_rect(pixmap, px, py, scale, scale, color);
+                }
+            }
+        }
+    }
+}
+
+/// Black or white, whichever reads better on `background`.
+fn label_color(background: &Color) -> Color {
+    if color_to_oklab(background).l > 0.6 {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    }
+}
+
+/// Render palettes one below the other, each as labeled swatches above a gradient strip.
+pub fn render_palette_preview(palettes: &[Palette], opts: &PreviewOptions) -> Pixmap {
+    let max_swatches = palettes.iter().map(|p| p.swatches.len()).max().unwrap_or(0) as u32;
+    let row_height = opts.swatch_height + opts.gradient_height;
+    let width = max_swatches.max(1) * opts.swatch_width + 2 * opts.padding;
+    let height = palettes.len().max(1) as u32 * (row_height + opts.padding) + opts.padding;
+    let mut pixmap = Pixmap::new(width, height).unwrap();
+    pixmap.fill(opts.background);
+
+    let scale = opts.font_scale();
+    let label_height = GLYPH_HEIGHT * scale;
+    for (row, palette) in palettes.iter().enumerate() {
+        let y = opts.padding + row as u32 * (row_height + opts.padding);
+        let colors = palette.colors();
+        for (i, color) in colors.iter().enumerate() {
+            let x = opts.padding + i as u32 * opts.swatch_width;
+            if !color.is_opaque() {
+                fill_checkerboard(&mut pixmap, x, y, opts.swatch_width, opts.swatch_height);
+            }
+            fill_rect(
+                &mut pixmap,
+                x,
+                y,
+                opts.swatch_width,
+                opts.swatch_height,
+                *color,
+            );
+            if label_height + 4 <= opts.swatch_height {
+                let label = format!("#{}", to_hex_color(color));
+                let (lx, ly) = (
+                    x + 2 * scale,
+                    y + opts.swatch_height - label_height - 2 * scale,
+                );
+                draw_text(&mut pixmap, &label, lx, ly, scale, label_color(color));
+            }
+        }
+
+        if colors.is_empty() || opts.gradient_height == 0 {
+            continue;
+        }
+        let gradient = PaletteGradient::new(&colors, opts.interpolation);
+        let strip_width = colors.len() as u32 * opts.swatch_width;
+        let strip_y = y + opts.swatch_height;
+        fill_checkerboard(
+            &mut pixmap,
+            opts.padding,
+            strip_y,
+            strip_width,
+            opts.gradient_height,
+        );
+        for dx in 0..strip_width {
+            let t = dx as f64 / (strip_width - 1).max(1) as f64;
+            let x = opts.padding + dx;
+            fill_rect(
+                &mut pixmap,
+                x,
+                strip_y,
+                1,
+                opts.gradient_height,
+                gradient.get(t),
+            );
+        }
+    }
+    pixmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(pixmap: &Pixmap, x: u32, y: u32) -> (u8, u8, u8) {
+        let p = pixmap.pixel(x, y).unwrap();
+        (p.red(), p.green(), p.blue())
+    }
+
+    #[test]
+    fn test_render_palette_preview_layout() {
+        let palettes = [
+            Palette::from_iter([
+                Color::from_rgba8(255, 0, 0, 255),
+                Color::from_rgba8(0, 0, 255, 255),
+            ]),
+            Palette::from_iter([Color::from_rgba8(0, 255, 0, 255)]),
+        ];
+        let opts = PreviewOptions::default();
+        let pixmap = render_palette_preview(&palettes, &opts);
+        let row = opts.swatch_height + opts.gradient_height + opts.padding;
+        assert_eq!(pixmap.width(), 2 * opts.swatch_width + 2 * opts.padding);
+        assert_eq!(pixmap.height(), 2 * row + opts.padding);
+
+        // top of each swatch, clear of the label
+        let (x0, y0) = (opts.padding + 1, opts.padding + 1);
+        assert_eq!(pixel(&pixmap, x0, y0), (255, 0, 0));
+        assert_eq!(pixel(&pixmap, x0 + opts.swatch_width, y0), (0, 0, 255));
+        assert_eq!(pixel(&pixmap, x0, y0 + row), (0, 255, 0));
+        // second palette is shorter, leaving background to its right
+        assert_eq!(
+            pixel(&pixmap, x0 + opts.swatch_width, y0 + row),
+            (32, 32, 32)
+        );
+
+        // gradient strip runs between the first and last colors
+        let strip_y = opts.padding + opts.swatch_height + 1;
+        let strip_end = opts.padding + 2 * opts.swatch_width - 1;
+        assert_eq!(pixel(&pixmap, opts.padding, strip_y), (255, 0, 0));
+        assert_eq!(pixel(&pixmap, strip_end, strip_y), (0, 0, 255));
+        let mid = pixel(&pixmap, opts.padding + opts.swatch_width, strip_y);
+        assert!(mid != (255, 0, 0) && mid != (0, 0, 255), "{:?}", mid);
+    }
+
+    #[test]
+    fn test_swatch_labels() {
+        let palettes = [
+            Palette::from_iter([Color::WHITE]),
+            Palette::from_iter([Color::BLACK]),
+        ];
+        let opts = PreviewOptions::default();
+        let pixmap = render_palette_preview(&palettes, &opts);
+        let row = opts.swatch_height + opts.gradient_height + opts.padding;
+        let swatch_pixels = |y0: u32| {
+            let mut pixels = vec![];
+            for y in y0..y0 + opts.swatch_height {
+                for x in opts.padding..opts.padding + opts.swatch_width {
+                    pixels.push(pixel(&pixmap, x, y));
+                }
+            }
+            pixels
+        };
+        assert!(swatch_pixels(opts.padding).contains(&(0, 0, 0)));
+        assert!(swatch_pixels(opts.padding + row).contains(&(255, 255, 255)));
+    }
+}
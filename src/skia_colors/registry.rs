@@ -0,0 +1,322 @@
+//! Palettes bundled from `assets/colors`, and the `--palette` options that select them.
+//!
+//! Binaries flatten [`PaletteArgs`] into their CLI.  Tools that want a default palette,
+//! lookup or different help text change it with clap's `mut_arg`, e.g.
+//!
+//! ```ignore
+//! #[command(mut_arg("palette", |arg| arg.default_value(DEFAULT_PALETTE_NAME)))]
+//! ```
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use clap::ValueEnum;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{
+    generate_palette, load_palette, parse_hex_palette, Harmony, Interpolation, LoadPaletteError,
+    Palette, PaletteGradient, PaletteLookup, PaletteOp, DEFAULT_HARMONY_COLORS,
+};
+
+/// Prefix for generated palette specs, e.g. `gen:triadic` or `gen:cosine:8`.
+const GENERATED_PREFIX: &str = "gen:";
+
+const CREDITS: &str = include_str!("../../assets/colors/CREDITS");
+
+/// A palette compiled into the binary.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BuiltinPalette {
+    pub name: &'static str,
+    file_name: &'static str,
+    contents: &'static str,
+}
+
+macro_rules! builtin {
+    ($name:literal) => {
+        BuiltinPalette {
+            name: $name,
+            file_name: concat!($name, ".hex"),
+            contents: include_str!(concat!("../../assets/colors/", $name, ".hex")),
+        }
+    };
+}
+
+/// Every palette in `assets/colors`.  `test_builtin_palettes_match_assets` fails when a
+/// `.hex` file is added there without an entry here.
+pub const BUILTIN_PALETTES: &[BuiltinPalette] = &[
+    builtin!("ocaso"),
+    builtin!("golden-haze"),
+    builtin!("dawnbringer-16"),
+    builtin!("mazzical-20"),
+];
+
+pub const DEFAULT_PALETTE_NAME: &str = "ocaso";
+
+impl BuiltinPalette {
+    /// Credit line for this palette from `assets/colors/CREDITS`.
+    pub fn credit(&self) -> Option<&'static str> {
+        let mut lines = CREDITS.lines();
+        lines.find(|l| l.trim() == self.file_name)?;
+        lines.next().map(str::trim)
+    }
+
+    pub fn palette(&self) -> Palette {
+        let mut palette: Palette = parse_hex_palette(self.contents)
+            .expect("bundled palettes are valid")
+            .into_iter()
+            .collect();
+        palette.name = Some(String::from(self.name));
+        palette
+    }
+}
+
+/// Look up a bundled palette by name, e.g. `"dawnbringer-16"`.
+pub fn builtin_palette(name: &str) -> Option<&'static BuiltinPalette> {
+    BUILTIN_PALETTES.iter().find(|p| p.name == name)
+}
+
+/// A palette selected on the command line: a bundled name, a file path, or a
+/// generator spec like `gen:triadic` / `gen:cosine:8`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteSpec {
+    Builtin(&'static BuiltinPalette),
+    Path(PathBuf),
+    Generated { harmony: Harmony, n_colors: usize },
+}
+
+impl PaletteSpec {
+    /// Load the palette.  Generated palettes are seeded from entropy.
+    pub fn load(&self) -> Result<Palette, LoadPaletteError> {
+        self.load_with_rng(&mut ChaCha8Rng::from_entropy())
+    }
+
+    /// Load the palette, seeding generated palettes from `rng` so they are reproducible.
+    pub fn load_with_rng<R: Rng>(&self, rng: &mut R) -> Result<Palette, LoadPaletteError> {
+        match self {
+            Self::Builtin(builtin) => Ok(builtin.palette()),
+            Self::Path(path) => load_palette(path),
+            Self::Generated { harmony, n_colors } => {
+                let mut rng = ChaCha8Rng::seed_from_u64(rng.gen());
+                Ok(generate_palette(*harmony, *n_colors, &mut rng))
+            }
+        }
+    }
+}
+
+// Palette options to flatten into a binary's CLI.
+#[derive(clap::Args, Debug, Clone)]
+pub struct PaletteArgs {
+    /// bundled palette name, palette file (.hex, .gpl, .pal or .ase) or generator (gen:triadic)
+    #[arg(long)]
+    pub palette: Option<PaletteSpec>,
+
+    /// palette operations applied in order after loading, e.g. sort:lum, reverse, rotate:2,
+    /// darken:0.1, desaturate:0.3, every:2 or pick:0,3
+    #[arg(long)]
+    pub palette_op: Vec<PaletteOp>,
+
+    /// map values onto palette entries (hard bands) or blend between them
+    #[arg(long, value_enum, default_value_t = PaletteLookup::Discrete)]
+    pub palette_lookup: PaletteLookup,
+
+    /// color space used for continuous palette lookup
+    #[arg(long, value_enum, default_value_t = Interpolation::Oklab)]
+    pub interpolation: Interpolation,
+}
+
+impl PaletteArgs {
+    /// Load the palette and apply the palette operations, or `None` if no palette was given.
+    pub fn load<R: Rng>(&self, rng: &mut R) -> Result<Option<Palette>, LoadPaletteError> {
+        self.palette
+            .as_ref()
+            .map(|spec| Ok(spec.load_with_rng(rng)?.apply_ops(&self.palette_op)?))
+            .transpose()
+    }
+
+    /// A gradient through the loaded palette, or `None` if no palette was given.
+    pub fn gradient<R: Rng>(
+        &self,
+        rng: &mut R,
+    ) -> Result<Option<PaletteGradient>, LoadPaletteError> {
+        let palette = self.load(rng)?;
+        Ok(palette.map(|p| PaletteGradient::new(&p.colors(), self.interpolation)))
+    }
+}
+
+fn parse_generated_spec(s: &str) -> Result<PaletteSpec, String> {
+    let (name, n_colors) = match s.split_once(':') {
+        Some((name, n)) => {
+            let n = n
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("invalid color count '{}'", n))?;
+            (name, n)
+        }
+        None => (s, DEFAULT_HARMONY_COLORS),
+    };
+    let harmony = Harmony::from_str(name, true).map_err(|_| {
+        let names: Vec<_> = Harmony::value_variants()
+            .iter()
+            .filter_map(|h| h.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        format!(
+            "unknown harmony '{}', expected one of: {}",
+            name,
+            names.join(", ")
+        )
+    })?;
+    Ok(PaletteSpec::Generated { harmony, n_colors })
+}
+
+impl Default for PaletteSpec {
+    fn default() -> Self {
+        Self::Builtin(builtin_palette(DEFAULT_PALETTE_NAME).unwrap())
+    }
+}
+
+impl FromStr for PaletteSpec {
+    type Err = String;
+
+    /// Bundled names take precedence; anything else is treated as a path, which must exist.
+    /// The file itself is only read when the palette is loaded.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(generated) = s.strip_prefix(GENERATED_PREFIX) {
+            return parse_generated_spec(generated);
+        }
+        if let Some(builtin) = builtin_palette(s) {
+            return Ok(Self::Builtin(builtin));
+        }
+        let path = PathBuf::from(s);
+        if !path.exists() {
+            let names: Vec<_> = BUILTIN_PALETTES.iter().map(|p| p.name).collect();
+            return Err(format!(
+                "'{}' is neither a bundled palette ({}) nor an existing file",
+                s,
+                names.join(", ")
+            ));
+        }
+        Ok(Self::Path(path))
+    }
+}
+
+impl fmt::Display for PaletteSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Builtin(builtin) => write!(f, "{}", builtin.name),
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Generated { harmony, n_colors } => write!(
+                f,
+                "{}{}:{}",
+                GENERATED_PREFIX,
+                harmony.to_possible_value().unwrap().get_name(),
+                n_colors
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_palette_spec_is_seeded() {
+        let spec: PaletteSpec = "gen:triadic:6".parse().unwrap();
+        let a = spec
+            .load_with_rng(&mut ChaCha8Rng::seed_from_u64(1))
+            .unwrap();
+        let b = spec
+            .load_with_rng(&mut ChaCha8Rng::seed_from_u64(1))
+            .unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.swatches.len(), 6);
+    }
+
+    #[test]
+    fn test_builtin_palettes() {
+        for builtin in BUILTIN_PALETTES {
+            let palette = builtin.palette();
+            assert!(!palette.swatches.is_empty(), "{} is empty", builtin.name);
+            assert_eq!(palette.name.as_deref(), Some(builtin.name));
+            let credit = builtin.credit().unwrap();
+            assert!(credit.ends_with(builtin.name), "bad credit: {}", credit);
+        }
+        assert_eq!(
+            builtin_palette("dawnbringer-16")
+                .unwrap()
+                .palette()
+                .swatches
+                .len(),
+            16
+        );
+        assert_eq!(builtin_palette("nope"), None);
+    }
+
+    #[test]
+    fn test_builtin_palettes_match_assets() {
+        let mut files: Vec<String> = std::fs::read_dir("assets/colors")
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".hex"))
+            .collect();
+        files.sort();
+        let mut builtin: Vec<String> = BUILTIN_PALETTES
+            .iter()
+            .map(|p| String::from(p.file_name))
+            .collect();
+        builtin.sort();
+        assert_eq!(builtin, files);
+    }
+
+    #[test]
+    fn test_palette_spec_from_str() {
+        assert_eq!(
+            "golden-haze".parse(),
+            Ok(PaletteSpec::Builtin(
+                builtin_palette("golden-haze").unwrap()
+            ))
+        );
+        assert_eq!(
+            "assets/colors/ocaso.hex".parse(),
+            Ok(PaletteSpec::Path(PathBuf::from("assets/colors/ocaso.hex")))
+        );
+        assert!("does/not/exist.gpl".parse::<PaletteSpec>().is_err());
+        assert_eq!(
+            "gen:split-complementary".parse(),
+            Ok(PaletteSpec::Generated {
+                harmony: Harmony::SplitComplementary,
+                n_colors: DEFAULT_HARMONY_COLORS
+            })
+        );
+        let spec: PaletteSpec = "gen:cosine:8".parse().unwrap();
+        assert_eq!(spec.to_string(), "gen:cosine:8");
+        assert!("gen:nope".parse::<PaletteSpec>().is_err());
+        assert!("gen:triadic:0".parse::<PaletteSpec>().is_err());
+        assert_eq!(PaletteSpec::default().to_string(), DEFAULT_PALETTE_NAME);
+    }
+
+    #[test]
+    fn test_palette_args() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            palette: PaletteArgs,
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let args = Cli::parse_from(["test"]).palette;
+        assert!(args.gradient(&mut rng).unwrap().is_none());
+
+        let args = Cli::parse_from(["test", "--palette", "ocaso", "--palette-op", "pick:0,1"]);
+        let gradient = args.palette.gradient(&mut rng).unwrap().unwrap();
+        let ocaso = builtin_palette("ocaso").unwrap().palette().colors();
+        assert_eq!(gradient.colors(), &ocaso[..2]);
+
+        let args = Cli::parse_from(["test", "--palette", "ocaso", "--palette-op", "pick:99"]);
+        assert!(args.palette.gradient(&mut rng).is_err());
+    }
+}